@@ -0,0 +1,52 @@
+//! Times [`build_graph`] over synthetic sites. Each site is crawled once from a
+//! local server to fill [`CrawlConfig::cache`], then timed crawling from the
+//! cache alone, so the time goes to bookkeeping and building the graph rather
+//! than to fetching. Each crawl is run a couple of times to warm up, and then
+//! timed over several runs.
+//!
+//! Run with `cargo bench`.
+
+mod common;
+
+use sprawl::{build_graph, CrawlConfig, PageResponse, Strategy};
+use std::{collections::HashSet, sync::Arc, time::Duration};
+use url::Url;
+
+/// Page `n` links to the next few pages, and back to the page at half its
+/// number, so there are several edges to most pages.
+fn links(n: usize, pages: usize) -> String {
+    let links = [n + 1, n + 2, n + 3, n / 2]
+        .into_iter()
+        .filter(|it| *it < pages)
+        .map(|it| it.to_string());
+    Vec::from_iter(links).join(" ")
+}
+
+/// Bodies are lists of page numbers rather than HTML, to keep parsing out of
+/// the measurement.
+fn get_children(response: &PageResponse, _depth: usize) -> Option<HashSet<Url>> {
+    let url = &response.final_url;
+    Some(HashSet::from_iter(
+        response
+            .body
+            .split_whitespace()
+            .map(|n| url.join(n).unwrap()),
+    ))
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    for pages in [100, 10_000, 100_000] {
+        let root = common::serve(move |n| links(n, pages)).await;
+        let mut config = CrawlConfig {
+            strategy: Strategy::BreadthFirst,
+            ..Default::default()
+        };
+        let warm = build_graph(&config, root.clone(), get_children).await;
+        assert_eq!(warm.stats.failed, 0);
+        config.cache = Arc::new(warm.pages);
+        config.cache_ttl = Duration::MAX;
+        let summary = common::measure(|| build_graph(&config, root.clone(), get_children)).await;
+        println!("{pages:>7} pages: {summary}");
+    }
+}
@@ -0,0 +1,95 @@
+//! What the benches share.
+
+use std::{
+    fmt,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
+};
+use url::Url;
+
+/// Serves `page(n)` at `/n` for each page `n`, returning the URL of `/0`.
+/// Connections are kept alive, so that a big site doesn't run out of ports.
+pub async fn serve(page: impl Fn(usize) -> String + Send + Sync + 'static) -> Url {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/0", listener.local_addr().unwrap());
+    let page = Arc::new(page);
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let page = page.clone();
+            tokio::spawn(async move {
+                let (read, mut write) = stream.into_split();
+                let mut lines = BufReader::new(read).lines();
+                let mut path = None;
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if let Some(request) = line.strip_prefix("GET /") {
+                        path = request.split(' ').next().and_then(|it| it.parse().ok());
+                    } else if line.is_empty() {
+                        let body = page(path.take().unwrap());
+                        let response = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\n\r\n{body}",
+                            body.len()
+                        );
+                        if write.write_all(response.as_bytes()).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+    });
+    url.parse().unwrap()
+}
+
+/// Runs discarded before measuring, to fill caches and connection pools.
+const WARM_UP: usize = 2;
+const RUNS: usize = 10;
+
+/// How long [`measure`] found something to take.
+pub struct Summary {
+    mean: Duration,
+    median: Duration,
+    std_dev: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} ± {:?} (median {:?}, {:?} to {:?}, {RUNS} runs)",
+            self.mean, self.std_dev, self.median, self.min, self.max
+        )
+    }
+}
+
+/// Times `run`, after warming up.
+pub async fn measure<F: Future>(mut run: impl FnMut() -> F) -> Summary {
+    for _ in 0..WARM_UP {
+        run().await;
+    }
+    let mut elapsed = Vec::with_capacity(RUNS);
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        run().await;
+        elapsed.push(start.elapsed());
+    }
+    elapsed.sort();
+    let secs = Vec::from_iter(elapsed.iter().map(Duration::as_secs_f64));
+    let mean = secs.iter().sum::<f64>() / RUNS as f64;
+    // of a sample, so divided by one less than the number of runs
+    let variance = secs.iter().map(|it| (it - mean).powi(2)).sum::<f64>() / (RUNS - 1) as f64;
+    Summary {
+        mean: Duration::from_secs_f64(mean),
+        median: elapsed[RUNS / 2],
+        std_dev: Duration::from_secs_f64(variance.sqrt()),
+        min: elapsed[0],
+        max: elapsed[RUNS - 1],
+    }
+}
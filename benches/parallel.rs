@@ -0,0 +1,53 @@
+//! Compares [`build_graph`] with [`build_graph_parallel`] over a wide site of
+//! real HTML, parsed with [`extract::links`]. Like the `build_graph` bench, the
+//! site is fetched once to fill [`CrawlConfig::cache`], so the time goes to
+//! parsing rather than fetching.
+//!
+//! Run with `cargo bench --bench parallel`.
+
+mod common;
+
+use sprawl::{build_graph, build_graph_parallel, extract, CrawlConfig, PageResponse};
+use std::{collections::HashSet, sync::Arc, time::Duration};
+use url::Url;
+
+const PAGES: usize = 2_000;
+/// Children per page, so the site is only a few levels deep.
+const WIDTH: usize = 50;
+
+/// Page `n` links to the next `WIDTH` pages of a tree, among paragraphs that
+/// make it slow enough to parse to matter.
+fn page(n: usize) -> String {
+    let mut body = String::from("<html><body>");
+    for child in (n * WIDTH + 1..=n * WIDTH + WIDTH).filter(|it| *it < PAGES) {
+        body.push_str(&format!(
+            r#"<p>Some words about page {child}, to <em>pad</em> it out.</p><a href="/{child}">{child}</a>"#
+        ));
+    }
+    body.push_str("</body></html>");
+    body
+}
+
+fn get_children(response: &PageResponse, _depth: usize) -> Option<HashSet<Url>> {
+    Some(HashSet::from_iter(extract::links(
+        &response.final_url,
+        &response.body,
+    )))
+}
+
+#[tokio::main]
+async fn main() {
+    let root = common::serve(page).await;
+    let mut config = CrawlConfig::default();
+    let warm = build_graph(&config, root.clone(), get_children).await;
+    assert_eq!(warm.pages.len(), PAGES);
+    config.cache = Arc::new(warm.pages);
+    config.cache_ttl = Duration::MAX;
+    let serial = common::measure(|| build_graph(&config, root.clone(), get_children)).await;
+    let parallel =
+        common::measure(|| build_graph_parallel(&config, root.clone(), get_children)).await;
+    println!(
+        "{PAGES} pages, {} threads\n  serial:   {serial}\n  parallel: {parallel}",
+        std::thread::available_parallelism().map_or(1, |it| it.get()),
+    );
+}
@@ -1,75 +1,618 @@
 use anyhow::Context;
 use clap::Parser;
+use futures::{Stream, StreamExt};
 use regex::Regex;
-use reqwest::Client;
-use soup::{NodeExt, QueryBuilderExt, Soup};
-use tracing::info;
+use reqwest::{header::HeaderValue, Identity};
+use sprawl::{CrawlConfig, CrawlEvent};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Write,
+    io::IsTerminal,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+use tracing::{error, info, warn, Level};
 use url::Url;
 
 #[derive(Parser)]
 #[clap(name = "sprawl")]
 struct Args {
-    #[clap(short, long)]
-    url: Url,
+    /// May be given more than once, in which case depth is counted from the
+    /// nearest
+    #[clap(short, long, required_unless_present = "seeds-file")]
+    url: Vec<Url>,
+    /// Newline separated URLs to crawl from as well, or `-` for stdin
+    #[clap(long)]
+    seeds_file: Option<PathBuf>,
     #[clap(short, long, default_value = "10")]
     depth: usize,
+    /// Stop visiting pages once this many have been found
+    #[clap(long)]
+    max_pages: Option<usize>,
     #[clap(short, long)]
     regex: Option<Regex>,
     #[clap(short, long)]
     limit_children: Option<usize>,
+    #[clap(long, default_value = "10")]
+    max_redirects: usize,
+    /// Resend requests answered with a 5xx or 429, or that couldn't connect,
+    /// up to this many times, instead of the profile's
+    #[clap(long)]
+    max_retries: Option<usize>,
+    /// Wait this many milliseconds before the first retry, doubling after
+    #[clap(long, default_value = "100")]
+    retry_delay_ms: u64,
+    /// The most requests in flight at once, instead of the profile's
+    #[clap(long)]
+    max_concurrency: Option<usize>,
+    /// Wait at least this many milliseconds between requests to the same host,
+    /// instead of the profile's
+    #[clap(long)]
+    delay: Option<u64>,
+    /// How hard to hit the servers, as a starting point for the other options
+    #[clap(long, arg_enum, default_value = "default")]
+    profile: Profile,
+    /// Only fetch pages under this URL
+    #[clap(long)]
+    scope_prefix: Option<Url>,
+    /// Treat www.example.com and example.com as the same host
+    #[clap(long)]
+    canonicalize_www: bool,
+    /// Treat `/dir/NAME` as `/dir/`, like `index.html`, may be given more than
+    /// once
+    #[clap(long)]
+    index_file: Vec<String>,
+    /// Guess at links in scripts too, which finds some that don't exist
+    #[clap(long)]
+    extract_from_scripts: bool,
+    /// Fold pages into their `<link rel="canonical">` pages
+    #[clap(long)]
+    merge_canonicals: bool,
+    /// Read `file://` URLs from disk, to crawl a directory of HTML
+    #[clap(long)]
+    read_files: bool,
+    /// Follow `rel="next"` and `rel="prev"` in `Link` headers
+    #[clap(long)]
+    follow_link_headers: bool,
+    /// Longer URLs aren't fetched
+    #[clap(long)]
+    max_url_length: Option<usize>,
+    /// Don't fetch pages that `robots.txt` disallows for the user agent
+    #[clap(long)]
+    respect_robots: bool,
+    /// Some sites (like example.com) reject requests without one
+    #[clap(long, default_value = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))]
+    user_agent: String,
+    /// Limits download bandwidth across all fetches
+    #[clap(long)]
+    max_bytes_per_sec: Option<u64>,
+    /// Don't download bodies bigger than this, like `500KB` or `5MB`
+    #[clap(long, parse(try_from_str = parse_size))]
+    max_file_size: Option<u64>,
+    /// Like `HOST=127.0.0.1:8080`, may be given more than once
+    #[clap(long, parse(try_from_str = parse_resolve))]
+    resolve: Vec<(String, SocketAddr)>,
+    /// Like `HOST=USER:PASSWORD`, may be given more than once
+    #[clap(long, parse(try_from_str = parse_basic_auth))]
+    basic_auth: Vec<(String, (String, String))>,
+    /// Like `fr-CH, fr;q=0.9`
+    #[clap(long)]
+    accept_language: Option<HeaderValue>,
+    /// Only for servers known to speak HTTP/2
+    #[clap(long)]
+    http2_prior_knowledge: bool,
+    /// A PKCS#12 client certificate, or a PEM one if `--identity-key` is given
+    #[clap(long)]
+    identity: Option<PathBuf>,
+    /// For a PKCS#12 `--identity`
+    #[clap(long, requires = "identity", default_value = "")]
+    identity_password: String,
+    /// The PEM private key for `--identity`
+    #[clap(long, requires = "identity")]
+    identity_key: Option<PathBuf>,
+    #[clap(long, arg_enum, default_value = "dot")]
+    format: Format,
+    /// Group `--format dot` output into a box for each of these
+    #[clap(long, arg_enum)]
+    cluster_by: Option<ClusterBy>,
+    /// Print this instead of the graph
+    #[clap(long, arg_enum)]
+    report: Option<Report>,
+    /// Write the graph or report here instead of to stdout
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+    /// Write tab separated lines of each page and its status here
+    #[clap(long)]
+    nodes_file: Option<PathBuf>,
+    /// Record progress here, and resume from it if it exists
+    #[clap(long)]
+    journal: Option<PathBuf>,
+    /// Show progress on a live dashboard instead of logging, if stderr is a
+    /// terminal
+    #[clap(long)]
+    tui: bool,
+    /// Save each page's body to a file under this directory
+    #[clap(long)]
+    save_bodies_to: Option<PathBuf>,
+    /// Print what the crawl would do as JSON, without crawling
+    #[clap(long)]
+    print_plan: bool,
+    /// Sample two levels from the first seed, and don't crawl if that suggests
+    /// there are more pages than this
+    #[clap(long)]
+    max_estimated_pages: Option<usize>,
+    /// Stop crawling after this many seconds, and output what was found
+    #[clap(long)]
+    timeout: Option<u64>,
+    /// Give up on hosts that don't accept a connection within this many
+    /// seconds
+    #[clap(long)]
+    connect_timeout: Option<u64>,
+}
+
+#[derive(Clone, Copy, clap::ArgEnum)]
+enum Format {
+    Dot,
+    /// A flowchart to paste into Markdown
+    Mermaid,
+    /// Tab separated lines of `from` and `to`
+    Edges,
+    /// A sitemap.xml of the pages on the same site as the first seed
+    Sitemap,
+    /// SQL that creates `nodes` and `edges` tables, to pipe into `sqlite3`
+    /// (`sprawl .. --format sql | sqlite3 crawl.db`)
+    Sql,
+    /// An SQLite database with the tables `--format sql` creates, written to
+    /// `--output` with the `sqlite3` shell
+    Sqlite,
+    /// Nodes with their status, and edges between their indices
+    Json,
+    /// For graph tools like Gephi
+    Graphml,
+}
+
+#[derive(Clone, Copy, clap::ArgEnum)]
+enum Profile {
+    Aggressive,
+    Default,
+    /// Two fetches at a time, one per host, retrying server errors
+    Polite,
+}
+
+#[derive(Clone, Copy, clap::ArgEnum)]
+enum ClusterBy {
+    Host,
+}
+
+#[derive(Clone, Copy, clap::ArgEnum)]
+enum Report {
+    /// Tab separated HTTPS pages and the HTTP pages they link to
+    MixedContent,
+    /// How many pages failed with each kind of error, most common first
+    Errors,
+    /// Tab separated depths and how many pages are that far from the nearest
+    /// seed
+    Depth,
+    /// JSON for d3's treemap of the pages and bytes under each host and path
+    Treemap,
+}
+
+fn parse_basic_auth(s: &str) -> anyhow::Result<(String, (String, String))> {
+    let (host, credentials) = s.split_once('=').context("expected HOST=USER:PASSWORD")?;
+    let (username, password) = credentials
+        .split_once(':')
+        .context("expected HOST=USER:PASSWORD")?;
+    Ok((host.into(), (username.into(), password.into())))
+}
+
+/// A number of bytes, with an optional `KB`, `MB` or `GB` suffix for powers of
+/// 1024.
+fn parse_size(s: &str) -> anyhow::Result<u64> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, suffix) = s.split_at(split);
+    let multiplier: u64 = match suffix.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        other => anyhow::bail!("unknown size suffix {other:?}"),
+    };
+    number
+        .parse::<u64>()
+        .context("expected a number of bytes")?
+        .checked_mul(multiplier)
+        .context("size is too big")
+}
+
+fn parse_resolve(s: &str) -> anyhow::Result<(String, SocketAddr)> {
+    let (host, addr) = s.split_once('=').context("expected HOST=ADDR")?;
+    Ok((host.into(), addr.parse()?))
+}
+
+fn read_identity(args: &Args) -> anyhow::Result<Option<Identity>> {
+    let Some(path) = &args.identity else {
+        return Ok(None);
+    };
+    let read = |path: &Path| {
+        std::fs::read(path).with_context(|| format!("Couldn't read {}", path.display()))
+    };
+    let identity = match &args.identity_key {
+        Some(key) => Identity::from_pkcs8_pem(&read(path)?, &read(key)?),
+        None => Identity::from_pkcs12_der(&read(path)?, &args.identity_password),
+    };
+    Ok(Some(identity.context("Couldn't load client certificate")?))
+}
+
+/// Invalid lines are skipped with a warning.
+fn read_seeds(path: &Path) -> anyhow::Result<Vec<Url>> {
+    let text = match path.to_str() {
+        Some("-") => std::io::read_to_string(std::io::stdin()).context("Couldn't read stdin")?,
+        _ => std::fs::read_to_string(path)
+            .with_context(|| format!("Couldn't read {}", path.display()))?,
+    };
+    let mut seeds = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match line.parse() {
+            Ok(url) => seeds.push(url),
+            Err(e) => warn!("Skipping line {} of {}: {e}", number + 1, path.display()),
+        }
+    }
+    Ok(seeds)
+}
+
+/// What the `--tui` dashboard shows.
+#[derive(Default)]
+struct Dashboard {
+    /// Successful requests, not counting redirects.
+    fetched: usize,
+    /// Requests that have been sent, but haven't finished.
+    in_flight: HashSet<Url>,
+    errors: usize,
+    skipped: usize,
+    /// Discovered but not fetched yet.
+    queued: HashSet<Url>,
+    seen: HashSet<Url>,
+    hosts: HashMap<String, usize>,
+    recent: VecDeque<Url>,
+    /// Ctrl-C was pressed, which the logs would say if they weren't hidden.
+    stopping: bool,
+}
+
+impl Dashboard {
+    const RECENT: usize = 10;
+
+    fn update(&mut self, event: CrawlEvent) {
+        match event {
+            CrawlEvent::FetchStarted { url } => {
+                self.in_flight.insert(url);
+            }
+            CrawlEvent::NodeFetched { url, result, .. } => {
+                // redirect hops and skipped pages weren't requested themselves
+                if self.in_flight.remove(&url) && result.is_ok() {
+                    self.fetched += 1;
+                }
+                self.queued.remove(&url);
+                if let Some(host) = url.host_str() {
+                    *self.hosts.entry(host.into()).or_default() += 1;
+                }
+                self.recent.push_front(url.clone());
+                self.recent.truncate(Self::RECENT);
+                self.seen.insert(url);
+            }
+            CrawlEvent::EdgeDiscovered { to, .. } if !self.seen.contains(&to) => {
+                self.queued.insert(to);
+            }
+            CrawlEvent::Error { .. } => self.errors += 1,
+            CrawlEvent::Skipped { url, .. } => {
+                self.skipped += 1;
+                self.queued.remove(&url);
+            }
+            CrawlEvent::DepthLimited { url, .. } => {
+                self.queued.remove(&url);
+            }
+            _ => {}
+        }
+    }
+
+    fn draw(&self) {
+        let mut hosts = Vec::from_iter(&self.hosts);
+        hosts.sort_by(|(a_host, a), (b_host, b)| b.cmp(a).then(a_host.cmp(b_host)));
+        // clear the screen and start from the top left
+        let mut screen = String::from("\x1b[2J\x1b[H");
+        if self.stopping {
+            screen += "Stopping the crawl, press Ctrl-C again to quit immediately\n\n";
+        }
+        screen += &format!(
+            "fetched {}  in flight {}  queued {}  errors {}  skipped {}\n\ntop hosts\n",
+            self.fetched,
+            self.in_flight.len(),
+            self.queued.len(),
+            self.errors,
+            self.skipped
+        );
+        for (host, count) in hosts.into_iter().take(5) {
+            screen += &format!("  {count:>6}  {host}\n");
+        }
+        screen += "\nrecent\n";
+        for url in &self.recent {
+            screen += &format!("  {url}\n");
+        }
+        eprint!("{screen}");
+    }
+}
+
+/// Redraws the dashboard on stderr until the crawl finishes, saying so once
+/// `stopping` finishes.
+async fn show_dashboard(
+    events: impl Stream<Item = CrawlEvent>,
+    stopping: impl std::future::Future<Output = ()>,
+) {
+    let mut dashboard = Dashboard::default();
+    tokio::pin!(events, stopping);
+    let mut tick = tokio::time::interval(Duration::from_millis(250));
+    loop {
+        tokio::select! {
+            event = events.next() => match event {
+                Some(CrawlEvent::Finished(_)) | None => break,
+                Some(event) => dashboard.update(event),
+            },
+            _ = &mut stopping, if !dashboard.stopping => {
+                dashboard.stopping = true;
+                dashboard.draw();
+            }
+            _ = tick.tick() => dashboard.draw(),
+        }
+    }
+    dashboard.draw();
+}
+
+/// Loads `sql` into a new database at `path`, replacing whatever was there.
+fn write_sqlite(path: &Path, sql: &str) -> anyhow::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            return Err(e).with_context(|| format!("Couldn't replace {}", path.display()))
+        }
+        _ => {}
+    }
+    let mut sqlite = Command::new("sqlite3")
+        .arg("-bail")
+        .arg(path)
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("Couldn't run sqlite3, which --format sqlite needs")?;
+    let mut stdin = sqlite.stdin.take().expect("stdin is piped");
+    std::io::Write::write_all(&mut stdin, sql.as_bytes())
+        .context("Couldn't send sqlite3 the graph")?;
+    // so that sqlite3 sees the end of the script
+    drop(stdin);
+    let status = sqlite.wait()?;
+    anyhow::ensure!(
+        status.success(),
+        "sqlite3 couldn't write {}: {status}",
+        path.display()
+    );
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    if matches!(args.format, Format::Sqlite) && args.output.is_none() {
+        anyhow::bail!("--format sqlite needs --output");
+    }
+    // logs would scroll the dashboard away, so only show it on a terminal
+    let tui = args.tui && std::io::stderr().is_terminal();
     tracing_subscriber::fmt()
         .with_writer(std::io::stderr)
+        .with_max_level(match tui {
+            true => Level::ERROR,
+            false => Level::INFO,
+        })
         .init();
-    let args = Args::parse();
-    let client = Client::builder()
-        // example.com requires this header
-        .user_agent(concat!(
-            env!("CARGO_PKG_NAME"),
-            "/",
-            env!("CARGO_PKG_VERSION"),
-        ))
-        .build()
-        .context("Couldn't construct client")?;
-    let (graph, _) = sprawl::build_graph(&client, args.url, move |url, body, depth| {
-        if depth >= args.depth {
-            return None;
-        }
-        let soup = Soup::new(body);
-        let children = soup
-            .tag("a")
-            .attr_name("href")
-            .find_all()
-            .map(|anchor| {
-                let href = anchor.get("href").expect("Already filtered by href");
-                match href.parse::<Url>() {
-                    Ok(url) => Ok(url),
-                    Err(url::ParseError::RelativeUrlWithoutBase) => url.join(&href),
-                    Err(e) => Err(e),
-                }
-            })
-            .filter_map(Result::ok)
-            .filter(|url| {
+    let profile = CrawlConfig::with_profile(match args.profile {
+        Profile::Aggressive => sprawl::Profile::Aggressive,
+        Profile::Default => sprawl::Profile::Default,
+        Profile::Polite => sprawl::Profile::Polite,
+    });
+    let mut config = CrawlConfig {
+        user_agent: Some(args.user_agent.clone()),
+        accept_language: args.accept_language.clone(),
+        max_redirects: args.max_redirects,
+        max_retries: args.max_retries.unwrap_or(profile.max_retries),
+        retry_base_delay: Duration::from_millis(args.retry_delay_ms),
+        per_host_delay: args
+            .delay
+            .map(Duration::from_millis)
+            .or(profile.per_host_delay),
+        max_concurrency: args.max_concurrency.unwrap_or(profile.max_concurrency),
+        respect_robots: args.respect_robots || profile.respect_robots,
+        max_depth: Some(args.depth),
+        max_pages: args.max_pages,
+        max_url_length: args.max_url_length,
+        scope_prefix: args.scope_prefix.clone(),
+        canonicalize_www: args.canonicalize_www,
+        index_files: args.index_file.clone(),
+        extract_from_scripts: args.extract_from_scripts,
+        follow_link_headers: args.follow_link_headers,
+        read_files: args.read_files,
+        merge_canonicals: args.merge_canonicals,
+        // for the `anchor_text` of edges
+        link_attributes: matches!(args.format, Format::Sql | Format::Sqlite),
+        max_body_bytes: args.max_file_size,
+        http2_prior_knowledge: args.http2_prior_knowledge,
+        max_bytes_per_sec: args.max_bytes_per_sec,
+        resolve: args.resolve.clone(),
+        basic_auth: args.basic_auth.iter().cloned().collect(),
+        journal: args.journal.clone(),
+        identity: read_identity(&args)?,
+        save_bodies_to: args.save_bodies_to.clone(),
+        max_duration: args.timeout.map(Duration::from_secs),
+        connect_timeout: args.connect_timeout.map(Duration::from_secs),
+        ..profile
+    };
+    config.client = Some(config.build_client().context("Couldn't construct client")?);
+    tokio::spawn({
+        let cancellation = config.cancellation.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                warn!("Stopping the crawl, press Ctrl-C again to quit immediately");
+                cancellation.cancel();
+            }
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(130);
+            }
+        }
+    });
+    let mut seeds = args.url.clone();
+    if let Some(path) = &args.seeds_file {
+        seeds.extend(read_seeds(path)?);
+    }
+    if seeds.is_empty() {
+        anyhow::bail!("No seeds to crawl from");
+    }
+    if args.print_plan {
+        // the client was only built from the config
+        let mut plan = CrawlConfig {
+            client: None,
+            ..config.clone()
+        }
+        .describe();
+        plan["seeds"] = serde_json::json!(seeds);
+        plan["scope"]["regex"] = serde_json::json!(args.regex.as_ref().map(Regex::as_str));
+        plan["limits"]["limit_children"] = serde_json::json!(args.limit_children);
+        println!("{}", serde_json::to_string_pretty(&plan)?);
+        return Ok(());
+    }
+    let get_children = {
+        let (regex, limit_children) = (args.regex.clone(), args.limit_children);
+        move |response: &sprawl::PageResponse, _| {
+            let links = sprawl::extract::links(&response.final_url, &response.body);
+            let children = links.into_iter().filter(|url| {
                 matches!(
-                    args.regex.as_ref().map(|re| re.is_match(url.as_str())),
+                    regex.as_ref().map(|re| re.is_match(url.as_str())),
                     Some(true)
                 )
-            })
-            .map(|mut url| {
-                url.set_fragment(None);
-                url
             });
-        match args.limit_children {
-            Some(limit) => Some(children.take(limit).collect()),
-            None => Some(children.collect()),
-        }
-    })
-    .await;
-    let graph = graph.map(|_, n| n.to_string(), |_, _| ());
-    println!("{:?}", petgraph::dot::Dot::new(&graph));
-    info!("Graph has {} nodes", graph.raw_nodes().len());
+            match limit_children {
+                Some(limit) => Some(children.take(limit).collect()),
+                None => Some(children.collect()),
+            }
+        }
+    };
+    if let Some(max) = args.max_estimated_pages {
+        let estimate =
+            sprawl::estimate_size(&config, seeds[0].clone(), 2, get_children.clone()).await;
+        let pages = estimate.pages.unwrap_or(usize::MAX);
+        info!(
+            "Estimated {pages} pages, growing {}x per level",
+            estimate.branching_factor
+        );
+        if pages > max {
+            anyhow::bail!("An estimated {pages} pages is more than {max}");
+        }
+    }
+    let dashboard = tui.then(|| {
+        let cancellation = config.cancellation.clone();
+        let stopping = async move { cancellation.cancelled().await };
+        tokio::spawn(show_dashboard(config.event_stream(), stopping))
+    });
+    let started = Instant::now();
+    let result = sprawl::build_graph_from_seeds(&config, seeds, get_children).await;
+    if let Some(dashboard) = dashboard {
+        dashboard.await?;
+    }
+    if let Some(timeout) = config.max_duration.filter(|it| started.elapsed() >= *it) {
+        warn!("Stopped after the {timeout:?} timeout, so the graph may be incomplete");
+    }
+    let mut failed = 0;
+    for (url, e) in result.failed_seeds() {
+        error!("Couldn't fetch {url}: {e}");
+        failed += 1;
+    }
+    if failed == result.seed_indices.len() {
+        anyhow::bail!("No seeds could be fetched");
+    }
+    let mut out = String::new();
+    match (args.report, args.format) {
+        (Some(Report::MixedContent), _) => {
+            for (page, resource) in sprawl::analysis::mixed_content(&result) {
+                writeln!(out, "{page}\t{resource}")?;
+            }
+        }
+        (Some(Report::Errors), _) => {
+            let mut errors = Vec::from_iter(&result.stats.errors);
+            errors.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+            for (category, count) in errors {
+                writeln!(out, "{count}\t{category}")?;
+            }
+        }
+        (Some(Report::Depth), _) => {
+            for (depth, count) in &result.stats.depth_histogram {
+                writeln!(out, "{depth}\t{count}")?;
+            }
+        }
+        (Some(Report::Treemap), _) => {
+            writeln!(
+                out,
+                "{}",
+                serde_json::to_string(&sprawl::analysis::path_tree(&result))?
+            )?;
+        }
+        (None, Format::Dot) if matches!(args.cluster_by, Some(ClusterBy::Host)) => {
+            out = sprawl::export::to_dot_by_host(&result.graph)
+        }
+        (None, Format::Dot) => {
+            let graph = result.graph.map(|_, n| n.to_string(), |_, _| ());
+            writeln!(out, "{:?}", petgraph::dot::Dot::new(&graph))?;
+        }
+        (None, Format::Mermaid) => out = sprawl::export::to_mermaid(&result.graph),
+        (None, Format::Edges) => out = sprawl::export::to_edge_list(&result.graph),
+        (None, Format::Sitemap) => {
+            let base = &result.graph[result.root_index];
+            out = sprawl::export::to_sitemap(&result, base)
+        }
+        (None, Format::Sql | Format::Sqlite) => out = sprawl::export::to_sql(&result),
+        (None, Format::Json) => writeln!(out, "{}", sprawl::export::to_json(&result))?,
+        (None, Format::Graphml) => out = sprawl::export::to_graphml(&result),
+    }
+    match &args.output {
+        Some(path) if args.report.is_none() && matches!(args.format, Format::Sqlite) => {
+            write_sqlite(path, &out)?
+        }
+        Some(path) => std::fs::write(path, out)
+            .with_context(|| format!("Couldn't write {}", path.display()))?,
+        None => print!("{out}"),
+    }
+    if let Some(path) = &args.nodes_file {
+        std::fs::write(path, sprawl::export::to_node_list(&result.pages))
+            .with_context(|| format!("Couldn't write {}", path.display()))?;
+    }
+    info!(
+        "Graph has {} nodes, from {} requests",
+        result.graph.node_count(),
+        result.stats.requests
+    );
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_size;
+
+    #[test]
+    fn size() {
+        assert_eq!(parse_size("100").unwrap(), 100);
+        assert_eq!(parse_size("5MB").unwrap(), 5 * 1024 * 1024);
+        assert_eq!(parse_size("2 kb").unwrap(), 2048);
+        assert_eq!(parse_size("1G").unwrap(), 1 << 30);
+        assert!(parse_size("5TB").is_err());
+        assert!(parse_size("MB").is_err());
+    }
+}
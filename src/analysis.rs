@@ -0,0 +1,293 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use petgraph::{
+    algo::{is_cyclic_directed, tarjan_scc},
+    graph::{DiGraph, NodeIndex},
+    visit::EdgeRef,
+    Direction,
+};
+use serde::Serialize;
+use url::Url;
+
+use crate::{export::fnv1a, CrawlResult, EdgeKind};
+
+/// The pages with links to `url`, sorted, such as where a broken link lives.
+/// `url` is looked up like [`CrawlResult::get`].
+pub fn referrers<M>(result: &CrawlResult<M>, url: &Url) -> Vec<Url> {
+    let Some(index) = result.node_by_url(url) else {
+        return Vec::new();
+    };
+    let mut referrers = Vec::from_iter(
+        result
+            .graph
+            .edges_directed(index, Direction::Incoming)
+            .filter(|edge| edge.weight().kind == EdgeKind::Link)
+            .map(|edge| result.graph[edge.source()].clone()),
+    );
+    referrers.sort();
+    referrers
+}
+
+/// Links from HTTPS pages to HTTP ones, sorted, whether or not the HTTP pages
+/// could be fetched.
+pub fn mixed_content<M>(result: &CrawlResult<M>) -> Vec<(Url, Url)> {
+    let mut pairs = Vec::from_iter(
+        result
+            .graph
+            .edge_references()
+            .filter(|edge| edge.weight().kind == EdgeKind::Link)
+            .map(|edge| (&result.graph[edge.source()], &result.graph[edge.target()]))
+            .filter(|(from, to)| from.scheme() == "https" && to.scheme() == "http")
+            .map(|(from, to)| (from.clone(), to.clone())),
+    );
+    pairs.sort();
+    pairs
+}
+
+/// Every page with how many other pages link to it, most linked to first,
+/// then by URL. Only [`EdgeKind::Link`]s count, however many times a page
+/// links to another.
+pub fn in_degree_ranking<M>(result: &CrawlResult<M>) -> Vec<(Url, usize)> {
+    let graph = &result.graph;
+    let mut ranking = Vec::from_iter(graph.node_indices().map(|index| {
+        let referrers = HashSet::<NodeIndex>::from_iter(
+            graph
+                .edges_directed(index, Direction::Incoming)
+                .filter(|edge| edge.weight().kind == EdgeKind::Link && edge.source() != index)
+                .map(|edge| edge.source()),
+        );
+        (graph[index].clone(), referrers.len())
+    }));
+    ranking.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    ranking
+}
+
+/// Every page with its PageRank over the [`EdgeKind::Link`]s, highest first,
+/// then by URL. The ranks add up to 1. Pages without links share their rank
+/// with every page, and a page that links to another several times counts
+/// once.
+pub fn pagerank<M>(result: &CrawlResult<M>) -> Vec<(Url, f64)> {
+    const DAMPING: f64 = 0.85;
+    const ITERATIONS: usize = 100;
+    const TOLERANCE: f64 = 1e-10;
+    let graph = &result.graph;
+    let count = graph.node_count();
+    let links = Vec::from_iter(graph.node_indices().map(|index| {
+        let mut targets = Vec::from_iter(
+            graph
+                .edges(index)
+                .filter(|edge| edge.weight().kind == EdgeKind::Link)
+                .map(|edge| edge.target().index()),
+        );
+        targets.sort();
+        targets.dedup();
+        targets
+    }));
+    let mut ranks = vec![1.0 / count as f64; count];
+    for _ in 0..ITERATIONS {
+        let dangling: f64 = (0..count)
+            .filter(|it| links[*it].is_empty())
+            .map(|it| ranks[it])
+            .sum();
+        let base = (1.0 - DAMPING + DAMPING * dangling) / count as f64;
+        let mut next = vec![base; count];
+        for (from, targets) in links.iter().enumerate() {
+            for to in targets {
+                next[*to] += DAMPING * ranks[from] / targets.len() as f64;
+            }
+        }
+        let change: f64 = ranks.iter().zip(&next).map(|(a, b)| (a - b).abs()).sum();
+        ranks = next;
+        if change < TOLERANCE {
+            break;
+        }
+    }
+    let mut ranking = Vec::from_iter(
+        graph
+            .node_indices()
+            .map(|index| (graph[index].clone(), ranks[index.index()])),
+    );
+    ranking.sort_by(|(a, a_rank), (b, b_rank)| b_rank.total_cmp(a_rank).then(a.cmp(b)));
+    ranking
+}
+
+/// A level of [`path_tree`]. Serializes as the hierarchy that d3's `treemap`
+/// expects, summing either `pages` or `bytes`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct PathNode {
+    /// A path segment, or a host (with its port) at the top level.
+    pub name: String,
+    /// The pages at or below this level.
+    pub pages: usize,
+    /// The size of the bodies of the pages at or below this level.
+    pub bytes: usize,
+    /// Sorted by name.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<PathNode>,
+}
+
+/// The pages that were fetched successfully, grouped by host and then each
+/// segment of their path, to see where the bulk of a site lives. The query is
+/// ignored, and pages that redirected elsewhere are only counted where they
+/// ended up. The root is named after nothing.
+pub fn path_tree<M>(result: &CrawlResult<M>) -> PathNode {
+    #[derive(Default)]
+    struct Level {
+        pages: usize,
+        bytes: usize,
+        children: BTreeMap<String, Level>,
+    }
+    fn into_node(name: String, level: Level) -> PathNode {
+        PathNode {
+            name,
+            pages: level.pages,
+            bytes: level.bytes,
+            children: Vec::from_iter(
+                level
+                    .children
+                    .into_iter()
+                    .map(|(name, level)| into_node(name, level)),
+            ),
+        }
+    }
+    let mut root = Level::default();
+    for (url, page) in &result.pages {
+        let Ok(response) = &page.result else {
+            continue;
+        };
+        if response.final_url != *url {
+            continue;
+        }
+        let host = match url.port() {
+            Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+            None => url.host_str().unwrap_or_default().to_owned(),
+        };
+        let segments = url.path().split('/').filter(|it| !it.is_empty());
+        let mut level = &mut root;
+        for name in std::iter::once(host).chain(segments.map(String::from)) {
+            level.pages += 1;
+            level.bytes += response.body.len();
+            level = level.children.entry(name).or_default();
+        }
+        level.pages += 1;
+        level.bytes += response.body.len();
+    }
+    into_node(String::new(), root)
+}
+
+/// Groups of pages whose bodies are nearly the same, such as those that only
+/// differ by a timestamp, or share a template. Each body's words and markup
+/// are hashed into a SimHash, and pages whose hashes differ in at most
+/// `threshold` of their 64 bits are grouped, along with anything they're
+/// grouped with in turn. Only pages that were fetched successfully count,
+/// under the URL they ended up at. Each group is sorted, and so are the
+/// groups. Every pair of pages is compared, so this is slow for big crawls.
+pub fn near_duplicates<M>(result: &CrawlResult<M>, threshold: u32) -> Vec<Vec<Url>> {
+    let pages = Vec::from_iter(result.pages.iter().filter_map(|(url, page)| {
+        let response = page.result.as_ref().ok()?;
+        match response.final_url != *url {
+            true => None,
+            false => Some((url, simhash(&response.body))),
+        }
+    }));
+    // union-find, with each page pointing towards the root of its group
+    let mut parents = Vec::from_iter(0..pages.len());
+    fn root(parents: &mut [usize], mut it: usize) -> usize {
+        while parents[it] != it {
+            parents[it] = parents[parents[it]];
+            it = parents[it];
+        }
+        it
+    }
+    for (a, (_, a_hash)) in pages.iter().enumerate() {
+        for (b, (_, b_hash)) in pages.iter().enumerate().skip(a + 1) {
+            if (a_hash ^ b_hash).count_ones() <= threshold {
+                let (a, b) = (root(&mut parents, a), root(&mut parents, b));
+                parents[a] = b;
+            }
+        }
+    }
+    let mut groups = HashMap::<usize, Vec<Url>>::new();
+    for (index, (url, _)) in pages.iter().enumerate() {
+        let root = root(&mut parents, index);
+        groups.entry(root).or_default().push((*url).clone());
+    }
+    let mut groups = Vec::from_iter(groups.into_values().filter(|it| it.len() > 1));
+    for group in &mut groups {
+        group.sort();
+    }
+    groups.sort();
+    groups
+}
+
+fn simhash(body: &str) -> u64 {
+    let mut weights = [0i64; 64];
+    for token in body
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|it| !it.is_empty())
+    {
+        let hash = fnv1a(token.to_lowercase().as_bytes());
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            match hash >> bit & 1 {
+                1 => *weight += 1,
+                _ => *weight -= 1,
+            }
+        }
+    }
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0, |hash, (bit, _)| hash | 1 << bit)
+}
+
+pub fn is_acyclic<E>(graph: &DiGraph<Url, E>) -> bool {
+    !is_cyclic_directed(graph)
+}
+
+/// One cycle for each group of pages that can all reach each other, starting
+/// from the page with the lowest index. A cycle doesn't repeat its first page
+/// at the end, so a page that links to itself is a cycle of one.
+///
+/// Listing every cycle could take exponentially long, so the cycle given is
+/// the shortest through its first page.
+pub fn cycles<E>(graph: &DiGraph<Url, E>) -> Vec<Vec<Url>> {
+    let mut cycles = Vec::new();
+    for component in tarjan_scc(graph) {
+        let members = HashSet::<NodeIndex>::from_iter(component.iter().copied());
+        let start = *component.iter().min().expect("components aren't empty");
+        if let Some(path) = shortest_cycle(graph, start, &members) {
+            cycles.push(Vec::from_iter(path.into_iter().map(|it| graph[it].clone())));
+        }
+    }
+    cycles.sort();
+    cycles
+}
+
+/// A breadth first search from `start` back to itself, within `members`.
+fn shortest_cycle<E>(
+    graph: &DiGraph<Url, E>,
+    start: NodeIndex,
+    members: &HashSet<NodeIndex>,
+) -> Option<Vec<NodeIndex>> {
+    let mut parents = HashMap::new();
+    let mut queue = VecDeque::from([start]);
+    while let Some(node) = queue.pop_front() {
+        for next in graph.neighbors(node) {
+            if next == start {
+                let mut path = vec![node];
+                while let Some(parent) = parents.get(path.last().unwrap()) {
+                    path.push(*parent);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if members.contains(&next) && !parents.contains_key(&next) {
+                parents.insert(next, node);
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,585 @@
+use futures::future::BoxFuture;
+use reqwest::{
+    header::{HeaderMap, HeaderValue, ACCEPT_LANGUAGE},
+    redirect, Client, ClientBuilder, Identity, RequestBuilder,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+use crate::{CrawlContext, CrawlEvent, FetchAttempt, NodeValue, PageResponse, PauseToken};
+
+pub type ShouldKeep = Arc<dyn Fn(&Url, &str) -> bool + Send + Sync>;
+pub type ContentExtractor = Arc<dyn Fn(&PageResponse, usize) -> Option<HashSet<Url>> + Send + Sync>;
+pub type RequestHook = Arc<dyn Fn(&Url, RequestBuilder) -> RequestBuilder + Send + Sync>;
+pub type OnFetch = Arc<dyn Fn(&Url) + Send + Sync>;
+pub type ShouldRetry = Arc<dyn Fn(&FetchAttempt<'_>) -> bool + Send + Sync>;
+pub type PreprocessBody = Arc<dyn Fn(&Url, String) -> String + Send + Sync>;
+pub type Normalize = Arc<dyn Fn(Url) -> Url + Send + Sync>;
+pub type RenderFallback = Arc<dyn Fn(&Url) -> BoxFuture<'static, Option<String>> + Send + Sync>;
+pub type Login = Arc<dyn Fn(Client) -> BoxFuture<'static, Result<(), String>> + Send + Sync>;
+
+#[derive(Clone)]
+pub struct CrawlConfig {
+    /// Used instead of building one from this config. Redirects are followed by
+    /// `sprawl` itself, so this client shouldn't follow them (see [`redirect::Policy::none`]).
+    pub client: Option<Client>,
+    /// Shared with other crawls, in place of `client` and `max_bytes_per_sec`,
+    /// so that they reuse connections (and their DNS lookups), draw on one
+    /// bandwidth budget, fetch each `robots.txt` once, and keep to each
+    /// host's delay between them.
+    pub context: Option<CrawlContext>,
+    /// Used instead of the default client for URLs with these hosts.
+    pub host_clients: HashMap<String, Client>,
+    /// Used instead of the default client for URLs with these schemes, unless
+    /// there's a matching entry in `host_clients`.
+    pub scheme_clients: HashMap<String, Client>,
+    /// Read `file://` URLs from disk instead of requesting them, to crawl a
+    /// directory of generated HTML. Directories are read as their
+    /// `index.html`. Off by default, so that pages on the web can't lead the
+    /// crawl to local files.
+    pub read_files: bool,
+    /// Sent with every request, whichever client makes it, so this also
+    /// overrides the `User-Agent` of `client` and the other clients above.
+    pub user_agent: Option<String>,
+    /// Sent as `Accept-Language`, for sites that serve different pages per
+    /// locale. To compare locales, crawl once with each. Like `user_agent`,
+    /// this is sent whichever client makes the request.
+    pub accept_language: Option<HeaderValue>,
+    /// A username and password for HTTP Basic auth, sent to URLs with these
+    /// hosts.
+    pub basic_auth: HashMap<String, (String, String)>,
+    /// A client certificate, for servers that require mutual TLS. Load one
+    /// with [`Identity::from_pkcs12_der`] or [`Identity::from_pkcs8_pem`].
+    pub identity: Option<Identity>,
+    /// Speak HTTP/2 without negotiating it first. Only for servers known to
+    /// support it, which plain `http://` servers often don't.
+    pub http2_prior_knowledge: bool,
+    /// Size HTTP/2 flow control windows from the measured bandwidth, which
+    /// helps big downloads from a single host over fast links.
+    pub http2_adaptive_window: bool,
+    /// The most idle connections kept open for reuse per host. Unlimited by
+    /// default.
+    pub pool_max_idle_per_host: Option<usize>,
+    /// Bodies are read no faster than this, across all fetches.
+    pub max_bytes_per_sec: Option<u64>,
+    /// Hosts that resolve to these addresses instead of asking DNS, for
+    /// crawling a staging server under its real name. A host may appear more
+    /// than once. For a custom resolver, supply a `client` built with
+    /// [`ClientBuilder::dns_resolver`].
+    pub resolve: Vec<(String, SocketAddr)>,
+    pub max_redirects: usize,
+    /// How many times a request may be sent again after the first, when
+    /// `should_retry` says so, or it couldn't connect or timed out.
+    pub max_retries: usize,
+    /// How long to wait before the first retry, doubling for each one after,
+    /// plus up to half again so that pages that failed together aren't all
+    /// retried together.
+    pub retry_base_delay: Duration,
+    /// Responses with other statuses are recorded as [`FetchError::Status`](crate::FetchError::Status),
+    /// with the responses in [`NodeValue::error_response`], and their links
+    /// aren't followed. Add codes here to crawl pages like a 401 that
+    /// still has useful links. Redirects with a `Location` are followed before
+    /// this is checked, so a 3xx only matters here if it has none, like a 304.
+    /// Defaults to every 2xx.
+    pub success_statuses: HashSet<u16>,
+    /// Decides whether to send a request again. By default, those answered with
+    /// a 5xx or 429 are retried.
+    pub should_retry: Option<ShouldRetry>,
+    pub strategy: Strategy,
+    /// The most fetches in flight at once, whatever the strategy, so a wide
+    /// site can't open thousands of connections. A permit is only held while
+    /// a page is fetched, not while its links are found. For
+    /// [`Strategy::BreadthFirst`] and [`Strategy::DepthFirst`], this also
+    /// bounds how many pages are being worked on, so they're fetched in order.
+    pub max_concurrency: usize,
+    /// The most fetches in flight at once to any one host, whatever the
+    /// strategy.
+    pub per_host_concurrency: Option<usize>,
+    /// The least time between starting fetches from any one host, however
+    /// many are allowed in flight. Other hosts are fetched from meanwhile.
+    /// Pages wait for their turn before taking a `max_concurrency` or
+    /// `per_host_concurrency` permit, so waiting doesn't hold up other hosts.
+    /// Redirects and retries within a fetch aren't spaced out. With
+    /// `respect_robots`, a longer `Crawl-delay` in `robots.txt` wins.
+    pub per_host_delay: Option<Duration>,
+    /// Don't fetch pages that their host's `/robots.txt` disallows for
+    /// `user_agent`, keeping them as nodes skipped with
+    /// [`SkipReason::Robots`](crate::SkipReason::Robots). Each host's
+    /// `robots.txt` is fetched once, before its first page. Hosts without one
+    /// allow everything. A `Crawl-delay` there is honoured like
+    /// `per_host_delay`.
+    pub respect_robots: bool,
+    /// Children of pages this many links away from the nearest seed aren't
+    /// crawled.
+    pub max_depth: Option<usize>,
+    /// Once this many pages are nodes, no more are visited, and those already
+    /// being fetched are finished. The rest are left in the
+    /// [`frontier`](crate::CrawlResult::frontier), and links to them aren't in
+    /// the graph. Pages from the `journal` count, and each redirect counts
+    /// once, however many hops it has.
+    pub max_pages: Option<usize>,
+    /// Once this many edges have been found, links to pages that aren't already
+    /// connected are dropped, and those pages aren't crawled from there. Pages
+    /// without edges to them aren't reached, so this bounds the page count as
+    /// well. Redirects are always recorded.
+    pub max_edges: Option<usize>,
+    /// Count the `<a href>`s on each page that lead to each child, as
+    /// [`Edge::count`](crate::Edge::count), instead of recording every link
+    /// once. Only `<a>` elements are counted, with a second parse of the page
+    /// by [`extract::anchors`](crate::extract::anchors), whatever
+    /// `get_children` returns. Children that aren't found as anchors still
+    /// count as one.
+    pub count_links: bool,
+    /// Record the `rel`, `target`, enclosing section and text of each
+    /// `<a href>` on the edge it leads to, as [`Edge::attributes`](crate::Edge::attributes).
+    /// Like [`count_links`](Self::count_links), this only sees `<a>`
+    /// elements, so children found some other way have no attributes.
+    pub link_attributes: bool,
+    /// Longer URLs are kept as nodes, but aren't fetched. Some pages generate
+    /// links that grow without bound.
+    pub max_url_length: Option<usize>,
+    /// Pages with longer bodies are kept as nodes, skipped with
+    /// [`SkipReason::BodyTooLarge`](crate::SkipReason::BodyTooLarge). They
+    /// aren't downloaded if their `Content-Length` is too big, and are
+    /// abandoned as soon as they're too long otherwise.
+    pub max_body_bytes: Option<u64>,
+    /// Bodies that haven't finished arriving this long after their headers
+    /// are abandoned as [`FetchError::BodyTimeout`](crate::FetchError::BodyTimeout),
+    /// so a server that trickles bytes can't hold up the crawl. Time spent
+    /// waiting on `max_bytes_per_sec` doesn't count.
+    pub body_read_timeout: Option<Duration>,
+    /// Connections to servers that don't accept them within this long fail
+    /// with [`FetchError::Connect`](crate::FetchError::Connect), so dead hosts
+    /// fail fast however patient `body_read_timeout` is. Only applies to
+    /// clients built from this config.
+    pub connect_timeout: Option<Duration>,
+    /// URLs whose path has any segment more often than this are kept as nodes,
+    /// but aren't fetched, catching traps like `/a/a/a/a/...`.
+    pub max_segment_repeats: Option<usize>,
+    /// URLs whose path has more segments than this are kept as nodes, but
+    /// aren't fetched, catching paths that grow without end.
+    pub max_path_segments: Option<usize>,
+    /// URLs outside this subtree are kept as nodes, but aren't fetched. Whether
+    /// or not it has a trailing slash, `https://example.com/v2` covers
+    /// `/v2` and `/v2/docs` but not `/v2beta`.
+    pub scope_prefix: Option<Url>,
+    /// Pages outside this are kept as nodes, but aren't fetched.
+    pub scope: Scope,
+    /// Discovered URLs with these hosts are rewritten to the given host before
+    /// they're crawled, for sites served under several names.
+    pub host_aliases: HashMap<String, String>,
+    /// Drop `www.` from every discovered host (after `host_aliases`), so
+    /// `www.example.com` and `example.com` are crawled once.
+    pub canonicalize_www: bool,
+    /// Lowercase every path, for servers like IIS where `/Page` and `/page`
+    /// are the same.
+    pub case_insensitive_paths: bool,
+    /// Paths ending in one of these file names are crawled as their directory,
+    /// so `/dir/index.html` and `/dir/` share a node. Which names a server
+    /// treats as the index varies, so none are by default. Try `index.html`,
+    /// `index.htm` and `default.aspx`.
+    pub index_files: Vec<String>,
+    /// Applied to every URL after the normalization above, to decide which
+    /// spellings are the same page, such as by sorting the query or dropping
+    /// session parameters. What it returns is fetched and names the node, and
+    /// the other spellings are kept in [`NodeValue::discovered_as`].
+    pub normalize: Option<Normalize>,
+    /// Called with each page after it's fetched. Pages for which this returns
+    /// `false` are kept as nodes, but their children aren't crawled.
+    pub should_keep: Option<ShouldKeep>,
+    /// Applied to every request just before it's sent, including those for
+    /// redirects, for signing requests or adding tokens that change.
+    pub request_hook: Option<RequestHook>,
+    /// Called once for each page that's about to be fetched, but not for its
+    /// redirects or retries, or for pages from `cache`. Every page is fetched
+    /// at most once per crawl, which this can check.
+    pub on_fetch: Option<OnFetch>,
+    /// Run once with the default client before anything is fetched, such as to
+    /// post credentials to a login form. For the session to last, the client
+    /// needs a cookie store (see `ClientBuilder::cookie_store` behind
+    /// reqwest's `cookies` feature), so supply it as `client`. If this fails,
+    /// nothing is crawled and every seed fails with
+    /// [`FetchError::Login`](crate::FetchError::Login).
+    pub login: Option<Login>,
+    /// Used instead of `get_children` for pages with these content types, such
+    /// as `application/xml` for sitemaps. Keys are lowercase MIME types without
+    /// parameters.
+    pub extractors: HashMap<String, ContentExtractor>,
+    /// Applied to each body after it's decoded and before links are extracted
+    /// from it, such as to unwrap HTML from a JSON envelope. The page's node
+    /// keeps the body that was fetched.
+    pub preprocess_body: Option<PreprocessBody>,
+    /// Called for pages where `get_children` found no children, such as those
+    /// whose links are added by JavaScript. If it returns a rendered body,
+    /// `get_children` is called again with that instead. The page's node keeps
+    /// the body that was fetched.
+    pub render_fallback: Option<RenderFallback>,
+    /// Also crawl root-relative paths found in string literals in scripts,
+    /// and the same-origin scripts that pages load, with
+    /// [`extract::script_links`](crate::extract::script_links) and
+    /// [`extract::script_paths`](crate::extract::script_paths). This finds
+    /// some links in single page apps, along with strings that only look like
+    /// paths, so expect more pages that don't exist. Pages with an entry in
+    /// `extractors` are left alone.
+    pub extract_from_scripts: bool,
+    /// Also crawl the `rel="next"` and `rel="prev"` targets of each page's
+    /// `Link` headers, for paginated APIs and archives that don't link their
+    /// pages in the body. They're recorded as [`EdgeKind::Link`](crate::EdgeKind::Link)s.
+    pub follow_link_headers: bool,
+    /// Once the crawl is done, fold each page that declares a
+    /// `<link rel="canonical">` into the page it names, as search engines do.
+    /// The canonical page takes over the other's edges, and lists its URL in
+    /// [`NodeValue::discovered_as`]. Pages are only folded into canonical pages
+    /// that were fetched and don't name another canonical page themselves.
+    pub merge_canonicals: bool,
+    /// Once cancelled, no new pages are fetched and those in flight are
+    /// abandoned, so the crawl returns what it has so far.
+    pub cancellation: CancellationToken,
+    /// If no page finishes within this long, the crawl stops as if it were
+    /// cancelled, to return what it has from a site that's hung. `cancellation`
+    /// itself isn't cancelled. Time spent paused counts too.
+    pub idle_timeout: Option<Duration>,
+    /// This long after the crawl starts, it stops as if it were cancelled, to
+    /// bound how long it runs. `cancellation` itself isn't cancelled.
+    pub max_duration: Option<Duration>,
+    /// While paused, no new fetches start, but those in flight finish.
+    pub pause: PauseToken,
+    /// Every page and edge is sent here as soon as it's added. Sending stops
+    /// quietly if the receiver is dropped.
+    pub events: Option<UnboundedSender<CrawlEvent>>,
+    /// Pages from an earlier crawl, like [`CrawlResult::pages`](crate::CrawlResult::pages). Those that were
+    /// fetched successfully less than `cache_ttl` ago aren't fetched again, so
+    /// a repeated crawl only fetches what's stale or new.
+    pub cache: Arc<HashMap<Url, NodeValue>>,
+    pub cache_ttl: Duration,
+    /// Pages from an earlier crawl, like the keys of
+    /// [`CrawlResult::pages`](crate::CrawlResult::pages), that are kept as
+    /// nodes but skipped with [`SkipReason::Known`](crate::SkipReason::Known),
+    /// so only pages that are new since then are fetched. Seeds are always
+    /// fetched. Known pages' links aren't read again, so new pages are only
+    /// found through seeds and other new pages.
+    pub known: Arc<HashSet<Url>>,
+    /// Every visited page is appended to this file, and a crawl started with
+    /// an existing file carries on from the pages it found without fetching
+    /// those it recorded. Resuming with a different config may miss pages, or
+    /// crawl pages the new config wouldn't have reached.
+    ///
+    /// If the file can't be opened, that's logged and the crawl goes ahead
+    /// without a journal.
+    pub journal: Option<PathBuf>,
+    /// A snapshot of every page and edge found so far is written to this path
+    /// at each interval, replacing the last one, and once more when the crawl
+    /// finishes. Snapshots are in the same format as `journal`, so a crawl can
+    /// be resumed from a copy of one. Each is written to a temporary file
+    /// beside the path first, so the path always holds a whole snapshot.
+    pub checkpoint: Option<(CheckpointInterval, PathBuf)>,
+    /// Every successfully fetched body is also written to a file under this
+    /// directory, at a path like `example.com/foo/bar.html` mirroring its URL.
+    /// Pages whose path ends in a slash, or that turn out to have pages below
+    /// them, are saved as `index.html` inside their directory.
+    pub save_bodies_to: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strategy {
+    /// Every discovered child is fetched as soon as its parent is, as far as
+    /// [`CrawlConfig::max_concurrency`] allows.
+    #[default]
+    Concurrent,
+    /// Pages are fetched in the order they were discovered, so shallower pages
+    /// go first.
+    BreadthFirst,
+    /// The first child of each page is followed all the way down before its
+    /// siblings are fetched.
+    DepthFirst,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scope {
+    /// Pages on any origin are fetched.
+    #[default]
+    Any,
+    /// Only pages with the scheme, host and port of one of the seeds, so
+    /// `http://example.com`, `https://example.com` and
+    /// `https://example.com:8443` are each crawled separately.
+    SameOrigin,
+}
+
+/// A starting point for [`CrawlConfig::with_profile`], trading speed against
+/// how hard the servers are hit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Profile {
+    /// Like [`Profile::Default`], with HTTP/2 windows tuned for throughput.
+    Aggressive,
+    /// The same as [`CrawlConfig::default`].
+    #[default]
+    Default,
+    /// Breadth first, two fetches at a time and one per host, started at least
+    /// a second apart, retrying pages that fail with a 5xx or 429 twice, and
+    /// respecting `robots.txt`.
+    Polite,
+}
+
+/// How often [`CrawlConfig::checkpoint`] is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointInterval {
+    Elapsed(Duration),
+    /// Whenever this many more pages have been added.
+    Pages(usize),
+}
+
+impl Default for CrawlConfig {
+    fn default() -> Self {
+        Self {
+            client: None,
+            context: None,
+            host_clients: HashMap::new(),
+            scheme_clients: HashMap::new(),
+            read_files: false,
+            user_agent: None,
+            accept_language: None,
+            basic_auth: HashMap::new(),
+            identity: None,
+            http2_prior_knowledge: false,
+            http2_adaptive_window: false,
+            pool_max_idle_per_host: None,
+            max_bytes_per_sec: None,
+            resolve: Vec::new(),
+            max_redirects: 10,
+            max_retries: 0,
+            retry_base_delay: Duration::from_millis(100),
+            success_statuses: HashSet::from_iter(200..300),
+            should_retry: None,
+            strategy: Strategy::default(),
+            max_concurrency: 16,
+            per_host_concurrency: None,
+            per_host_delay: None,
+            respect_robots: false,
+            max_depth: None,
+            max_pages: None,
+            max_edges: None,
+            count_links: false,
+            link_attributes: false,
+            max_url_length: None,
+            max_body_bytes: None,
+            body_read_timeout: None,
+            connect_timeout: None,
+            max_segment_repeats: None,
+            max_path_segments: None,
+            scope_prefix: None,
+            scope: Scope::Any,
+            host_aliases: HashMap::new(),
+            canonicalize_www: false,
+            case_insensitive_paths: false,
+            index_files: Vec::new(),
+            normalize: None,
+            should_keep: None,
+            request_hook: None,
+            on_fetch: None,
+            login: None,
+            extractors: HashMap::new(),
+            preprocess_body: None,
+            render_fallback: None,
+            extract_from_scripts: false,
+            follow_link_headers: false,
+            merge_canonicals: false,
+            cancellation: CancellationToken::new(),
+            idle_timeout: None,
+            max_duration: None,
+            pause: PauseToken::new(),
+            events: None,
+            cache: Default::default(),
+            cache_ttl: Duration::ZERO,
+            known: Default::default(),
+            journal: None,
+            checkpoint: None,
+            save_bodies_to: None,
+        }
+    }
+}
+
+impl CrawlConfig {
+    /// The settings of `profile`, to override as needed.
+    pub fn with_profile(profile: Profile) -> Self {
+        match profile {
+            Profile::Aggressive => Self {
+                http2_adaptive_window: true,
+                ..Default::default()
+            },
+            Profile::Default => Self::default(),
+            Profile::Polite => Self {
+                strategy: Strategy::BreadthFirst,
+                max_concurrency: 2,
+                per_host_concurrency: Some(1),
+                per_host_delay: Some(Duration::from_secs(1)),
+                respect_robots: true,
+                max_retries: 2,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// gzip, brotli and deflate decompression are always enabled, so pages from
+    /// servers that compress unconditionally are still read back as text.
+    pub fn client_builder(&self) -> ClientBuilder {
+        let mut builder = Client::builder()
+            .gzip(true)
+            .brotli(true)
+            .deflate(true)
+            .redirect(redirect::Policy::none())
+            .http2_adaptive_window(self.http2_adaptive_window);
+        if self.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+        if let Some(accept_language) = &self.accept_language {
+            builder = builder.default_headers(HeaderMap::from_iter([(
+                ACCEPT_LANGUAGE,
+                accept_language.clone(),
+            )]));
+        }
+        if let Some(identity) = &self.identity {
+            builder = builder.identity(identity.clone());
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        let mut addrs = HashMap::<_, Vec<_>>::new();
+        for (host, addr) in &self.resolve {
+            addrs.entry(host.as_str()).or_default().push(*addr);
+        }
+        for (host, addrs) in addrs {
+            builder = builder.resolve_to_addrs(host, &addrs);
+        }
+        match &self.user_agent {
+            Some(user_agent) => builder.user_agent(user_agent),
+            None => builder,
+        }
+    }
+
+    pub fn build_client(&self) -> reqwest::Result<Client> {
+        self.client_builder().build()
+    }
+
+    /// What a crawl with this config would do, as JSON to review or log before
+    /// it runs. Hooks and clients are only listed by whether they're set, and
+    /// credentials are left out, though the hosts they're for aren't.
+    pub fn describe(&self) -> serde_json::Value {
+        let secs = |duration: Option<Duration>| duration.map(|it| it.as_secs_f64());
+        let mut hooks = Vec::new();
+        for (name, set) in [
+            ("should_retry", self.should_retry.is_some()),
+            ("normalize", self.normalize.is_some()),
+            ("should_keep", self.should_keep.is_some()),
+            ("request_hook", self.request_hook.is_some()),
+            ("on_fetch", self.on_fetch.is_some()),
+            ("login", self.login.is_some()),
+            ("preprocess_body", self.preprocess_body.is_some()),
+            ("render_fallback", self.render_fallback.is_some()),
+        ] {
+            if set {
+                hooks.push(name);
+            }
+        }
+        let mut extractors = Vec::from_iter(self.extractors.keys());
+        extractors.sort();
+        let mut basic_auth = Vec::from_iter(self.basic_auth.keys());
+        basic_auth.sort();
+        let mut success_statuses = Vec::from_iter(&self.success_statuses);
+        success_statuses.sort();
+        serde_json::json!({
+            "scope": {
+                "scope": format!("{:?}", self.scope),
+                "scope_prefix": self.scope_prefix,
+                "max_depth": self.max_depth,
+                "read_files": self.read_files,
+                "known_pages": self.known.len(),
+            },
+            "limits": {
+                "max_pages": self.max_pages,
+                "max_edges": self.max_edges,
+                "max_url_length": self.max_url_length,
+                "max_body_bytes": self.max_body_bytes,
+                "max_segment_repeats": self.max_segment_repeats,
+                "max_path_segments": self.max_path_segments,
+                "max_duration": secs(self.max_duration),
+                "idle_timeout": secs(self.idle_timeout),
+            },
+            "politeness": {
+                "strategy": format!("{:?}", self.strategy),
+                "max_concurrency": self.max_concurrency,
+                "per_host_concurrency": self.per_host_concurrency,
+                "per_host_delay": secs(self.per_host_delay),
+                "respect_robots": self.respect_robots,
+                "max_bytes_per_sec": self.max_bytes_per_sec,
+                "max_retries": self.max_retries,
+                "retry_base_delay": self.retry_base_delay.as_secs_f64(),
+                "user_agent": self.user_agent,
+            },
+            "fetching": {
+                "custom_client": self.client.is_some() || self.context.is_some(),
+                "host_clients": self.host_clients.len(),
+                "scheme_clients": self.scheme_clients.len(),
+                "max_redirects": self.max_redirects,
+                "success_statuses": success_statuses,
+                "body_read_timeout": secs(self.body_read_timeout),
+                "connect_timeout": secs(self.connect_timeout),
+                "basic_auth_hosts": basic_auth,
+                "client_identity": self.identity.is_some(),
+                "cached_pages": self.cache.len(),
+            },
+            "normalization": {
+                "host_aliases": self.host_aliases,
+                "canonicalize_www": self.canonicalize_www,
+                "case_insensitive_paths": self.case_insensitive_paths,
+                "index_files": self.index_files,
+                "merge_canonicals": self.merge_canonicals,
+            },
+            "extraction": {
+                "extractors": extractors,
+                "extract_from_scripts": self.extract_from_scripts,
+                "follow_link_headers": self.follow_link_headers,
+                "count_links": self.count_links,
+                "link_attributes": self.link_attributes,
+            },
+            "hooks": hooks,
+            "outputs": {
+                "journal": self.journal,
+                "checkpoint": self.checkpoint.as_ref().map(|(_, path)| path),
+                "save_bodies_to": self.save_bodies_to,
+            },
+        })
+    }
+
+    pub(crate) fn client_for<'a>(&'a self, url: &Url, default: &'a Client) -> &'a Client {
+        url.host_str()
+            .and_then(|host| self.host_clients.get(host))
+            .or_else(|| self.scheme_clients.get(url.scheme()))
+            .unwrap_or(default)
+    }
+
+    pub(crate) fn emit(&self, event: CrawlEvent) {
+        if let Some(events) = &self.events {
+            // nobody's listening
+            let _ = events.send(event);
+        }
+    }
+
+    /// Panics if a client can't be built, like [`Client::new`].
+    pub(crate) fn client(&self) -> Client {
+        match &self.client {
+            Some(client) => client.clone(),
+            None => self.build_client().expect("couldn't build client"),
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use reqwest::Client;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::{sync::OnceCell, time::Instant};
+use url::Origin;
+
+use crate::{robots::Robots, throttle::Throttle, CrawlConfig};
+
+/// Each origin's `robots.txt`, for [`CrawlConfig::respect_robots`]. Filled by
+/// whoever gets there first.
+pub(crate) type RobotsCache = Arc<Mutex<HashMap<Origin, Arc<OnceCell<Robots>>>>>;
+
+/// When each host may next be fetched from, for
+/// [`CrawlConfig::per_host_delay`] and `Crawl-delay`s. Taken by the fetch
+/// that's waiting for it.
+pub(crate) type NextFetch = Arc<Mutex<HashMap<String, Instant>>>;
+
+/// State that outlives a single crawl, for running many against the same
+/// hosts, such as from a service. Shared by clones, like [`PauseToken`](crate::PauseToken).
+#[derive(Clone)]
+pub struct CrawlContext {
+    pub(crate) client: Client,
+    pub(crate) throttle: Option<Arc<Throttle>>,
+    pub(crate) robots: RobotsCache,
+    pub(crate) next_fetch: NextFetch,
+}
+
+impl CrawlContext {
+    /// Builds the client from `config`, or uses its
+    /// [`client`](field@CrawlConfig::client), and starts a budget of
+    /// [`CrawlConfig::max_bytes_per_sec`]. Every crawl given this context uses
+    /// these instead of its own.
+    ///
+    /// The crawls also share each host's `robots.txt`, which is only fetched
+    /// once, so they should have the same [`CrawlConfig::user_agent`]. Their
+    /// [`CrawlConfig::per_host_delay`]s and `Crawl-delay`s space out fetches
+    /// from all of them, even when they run at the same time.
+    ///
+    /// Panics if a client can't be built, like [`Client::new`].
+    pub fn new(config: &CrawlConfig) -> Self {
+        Self {
+            client: config.client(),
+            throttle: config.max_bytes_per_sec.map(Throttle::new).map(Arc::new),
+            robots: Default::default(),
+            next_fetch: Default::default(),
+        }
+    }
+}
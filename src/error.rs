@@ -0,0 +1,168 @@
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum FetchError {
+    /// The request failed for some other reason than those below, or the page
+    /// couldn't be read from disk.
+    Other(String),
+    /// The server couldn't be connected to, including within
+    /// [`CrawlConfig::connect_timeout`](crate::CrawlConfig::connect_timeout).
+    Connect(String),
+    /// The request timed out once connected.
+    Timeout(String),
+    /// The body couldn't be decompressed, like a `Content-Encoding: gzip` body
+    /// that isn't gzip.
+    Decode(String),
+    RedirectLimitExceeded {
+        limit: usize,
+    },
+    /// The body was cut short, but `bytes` holds what arrived beforehand.
+    PartialBody {
+        error: String,
+        bytes: Vec<u8>,
+    },
+    /// The body took longer to arrive than
+    /// [`CrawlConfig::body_read_timeout`](crate::CrawlConfig::body_read_timeout),
+    /// but `bytes` holds what arrived beforehand.
+    BodyTimeout {
+        bytes: Vec<u8>,
+    },
+    /// The crawl was cancelled before this page finished downloading.
+    Cancelled,
+    /// The page wasn't fetched at all.
+    Skipped(SkipReason),
+    /// The final response's status wasn't in
+    /// [`CrawlConfig::success_statuses`](crate::CrawlConfig::success_statuses).
+    Status(#[serde(with = "status_code")] StatusCode),
+    /// [`CrawlConfig::login`](crate::CrawlConfig::login) failed, so the crawl
+    /// didn't start.
+    Login(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum SkipReason {
+    /// See [`CrawlConfig::max_url_length`](crate::CrawlConfig::max_url_length).
+    UrlTooLong { limit: usize },
+    /// See [`CrawlConfig::max_segment_repeats`](crate::CrawlConfig::max_segment_repeats).
+    RepeatedPathSegment { segment: String, limit: usize },
+    /// See [`CrawlConfig::max_path_segments`](crate::CrawlConfig::max_path_segments).
+    TooManyPathSegments { limit: usize },
+    /// See [`CrawlConfig::scope_prefix`](crate::CrawlConfig::scope_prefix) and
+    /// [`CrawlConfig::scope`](crate::CrawlConfig::scope).
+    OutOfScope,
+    /// See [`CrawlConfig::max_body_bytes`](crate::CrawlConfig::max_body_bytes).
+    /// `size` is the `Content-Length`, if the server sent one.
+    BodyTooLarge { size: Option<u64>, limit: u64 },
+    /// See [`CrawlConfig::known`](crate::CrawlConfig::known).
+    Known,
+    /// See [`CrawlConfig::respect_robots`](crate::CrawlConfig::respect_robots).
+    Robots,
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Other(e)
+            | FetchError::Connect(e)
+            | FetchError::Timeout(e)
+            | FetchError::Decode(e) => f.write_str(e),
+            FetchError::RedirectLimitExceeded { limit } => {
+                write!(f, "exceeded the limit of {limit} redirects")
+            }
+            FetchError::PartialBody { error, bytes } => {
+                write!(f, "{error} (after {} bytes)", bytes.len())
+            }
+            FetchError::BodyTimeout { bytes } => {
+                write!(
+                    f,
+                    "timed out reading the body (after {} bytes)",
+                    bytes.len()
+                )
+            }
+            FetchError::Cancelled => f.write_str("cancelled"),
+            FetchError::Skipped(reason) => write!(f, "skipped: {reason}"),
+            FetchError::Status(status) => write!(f, "status {}", status.as_u16()),
+            FetchError::Login(e) => write!(f, "login failed: {e}"),
+        }
+    }
+}
+
+impl FetchError {
+    /// A short name for the kind of error, for counting them. Statuses are
+    /// distinguished, like `status 404`, but the other variants aren't looked
+    /// into.
+    pub fn category(&self) -> String {
+        match self {
+            FetchError::Other(_) => "other".into(),
+            FetchError::Connect(_) => "connect".into(),
+            FetchError::Timeout(_) => "timeout".into(),
+            FetchError::Decode(_) => "decode".into(),
+            FetchError::RedirectLimitExceeded { .. } => "redirect limit".into(),
+            FetchError::PartialBody { .. } => "partial body".into(),
+            FetchError::BodyTimeout { .. } => "body timeout".into(),
+            FetchError::Cancelled => "cancelled".into(),
+            FetchError::Skipped(_) => "skipped".into(),
+            FetchError::Status(status) => format!("status {}", status.as_u16()),
+            FetchError::Login(_) => "login".into(),
+        }
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::UrlTooLong { limit } => write!(f, "url is longer than {limit}"),
+            SkipReason::RepeatedPathSegment { segment, limit } => {
+                write!(f, "path repeats {segment:?} more than {limit} times")
+            }
+            SkipReason::TooManyPathSegments { limit } => {
+                write!(f, "path has more than {limit} segments")
+            }
+            SkipReason::OutOfScope => f.write_str("out of scope"),
+            SkipReason::Known => f.write_str("already known"),
+            SkipReason::Robots => f.write_str("disallowed by robots.txt"),
+            SkipReason::BodyTooLarge {
+                size: Some(size),
+                limit,
+            } => write!(f, "body of {size} bytes is larger than {limit}"),
+            SkipReason::BodyTooLarge { size: None, limit } => {
+                write!(f, "body is larger than {limit} bytes")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(e: reqwest::Error) -> Self {
+        // a connect timeout is both
+        if e.is_connect() {
+            FetchError::Connect(e.to_string())
+        } else if e.is_timeout() {
+            FetchError::Timeout(e.to_string())
+        } else if e.is_decode() {
+            FetchError::Decode(e.to_string())
+        } else {
+            FetchError::Other(e.to_string())
+        }
+    }
+}
+
+/// A [`StatusCode`] as its number, as journals have always held it.
+mod status_code {
+    use reqwest::StatusCode;
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(status: &StatusCode, serializer: S) -> Result<S::Ok, S::Error> {
+        status.as_u16().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<StatusCode, D::Error> {
+        StatusCode::from_u16(u16::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
@@ -0,0 +1,113 @@
+use futures::{stream, Stream, StreamExt};
+use petgraph::graph::DiGraph;
+use std::collections::{BTreeMap, HashMap};
+use tokio::sync::mpsc;
+use url::Url;
+
+use crate::{CrawlConfig, EdgeKind, FetchError, PageResponse, SkipReason};
+
+/// Sent through [`CrawlConfig::events`] as the crawl goes, for building
+/// something incrementally or showing progress.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CrawlEvent {
+    /// A request for a page is being sent, having waited for any delays and
+    /// limits. Its [`CrawlEvent::NodeFetched`] says how it went. Pages that
+    /// are skipped or cached aren't requested.
+    FetchStarted { url: Url },
+    /// A page was added to the graph, whether or not it could be fetched.
+    /// `result` is its [`NodeValue::result`](crate::NodeValue::result), with
+    /// the response if it was fetched, so it can be processed straight away.
+    NodeFetched {
+        url: Url,
+        depth: usize,
+        result: Result<PageResponse, FetchError>,
+    },
+    /// An edge was added to the graph. Its target might not have been fetched
+    /// yet.
+    EdgeDiscovered { from: Url, to: Url, kind: EdgeKind },
+    /// Follows the [`CrawlEvent::NodeFetched`] of a page that couldn't be
+    /// fetched.
+    Error { url: Url, error: FetchError },
+    /// A page's children weren't crawled because of
+    /// [`CrawlConfig::max_depth`].
+    DepthLimited { url: Url, depth: usize },
+    /// Follows the [`CrawlEvent::NodeFetched`] of a page that wasn't fetched
+    /// on purpose.
+    Skipped { url: Url, reason: SkipReason },
+    /// Always the last event.
+    Finished(CrawlStats),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CrawlStats {
+    pub pages: usize,
+    pub edges: usize,
+    /// Pages that couldn't be fetched, not counting those that were skipped.
+    pub failed: usize,
+    /// How many of the `failed` pages had each [`FetchError::category`].
+    pub errors: BTreeMap<String, usize>,
+    pub skipped: usize,
+    /// See [`CrawlResult::frontier`](crate::CrawlResult::frontier).
+    pub frontier: usize,
+    /// How many pages are at each [`NodeValue::depth`](crate::NodeValue::depth),
+    /// to see how wide or deep a site is.
+    pub depth_histogram: BTreeMap<usize, usize>,
+    /// Requests that were sent, counting each retry and redirect, for how hard
+    /// the crawl hit the servers. Pages from [`CrawlConfig::cache`] don't
+    /// count, and neither does [`CrawlConfig::login`].
+    pub requests: usize,
+}
+
+/// Folds the events of a crawl, like those from [`crawl_stream`](crate::crawl_stream),
+/// into the graph [`build_graph`](crate::build_graph) would have returned, and
+/// each page's result. Edges to pages that were never added are left out, as
+/// they are from [`CrawlResult::graph`](crate::CrawlResult::graph).
+pub async fn collect_graph(
+    events: impl Stream<Item = CrawlEvent>,
+) -> (
+    DiGraph<Url, EdgeKind>,
+    HashMap<Url, Result<PageResponse, FetchError>>,
+) {
+    let mut pages = HashMap::new();
+    let mut edges = Vec::new();
+    let mut events = std::pin::pin!(events);
+    while let Some(event) = events.next().await {
+        match event {
+            CrawlEvent::NodeFetched { url, result, .. } => {
+                pages.insert(url, result);
+            }
+            CrawlEvent::EdgeDiscovered { from, to, kind } => edges.push((from, to, kind)),
+            CrawlEvent::Finished(_) => break,
+            _ => {}
+        }
+    }
+    let mut graph = DiGraph::with_capacity(pages.len(), edges.len());
+    let indices = HashMap::<_, _>::from_iter(
+        pages
+            .keys()
+            .map(|url| (url.clone(), graph.add_node(url.clone()))),
+    );
+    for (from, to, kind) in edges {
+        if let (Some(from), Some(to)) = (indices.get(&from), indices.get(&to)) {
+            graph.add_edge(*from, *to, kind);
+        }
+    }
+    (graph, pages)
+}
+
+impl CrawlConfig {
+    /// Sets up [`CrawlConfig::events`], returning everything sent there. The
+    /// stream ends once the config (and any clones of it) are dropped, so to
+    /// consume it while crawling, move the config into the crawl's future
+    /// and drop it there.
+    pub fn event_stream(&mut self) -> impl Stream<Item = CrawlEvent> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.events = Some(sender);
+        stream::unfold(receiver, |mut receiver| async move {
+            let event = receiver.recv().await?;
+            Some((event, receiver))
+        })
+    }
+}
@@ -0,0 +1,363 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use petgraph::{graph::DiGraph, visit::EdgeRef};
+use url::Url;
+
+use crate::{CrawlResult, Edge, EdgeKind, NodeValue};
+
+/// A Mermaid `graph TD` block. Mermaid ids can't contain most of a URL, so
+/// nodes are numbered, each labelled with its URL. Redirects are dotted, and
+/// canonical links are thick.
+pub fn to_mermaid(graph: &DiGraph<Url, Edge>) -> String {
+    let mut out = String::from("graph TD\n");
+    for index in graph.node_indices() {
+        // quotes end a label, and Mermaid has entity codes instead of escapes
+        let label = graph[index].as_str().replace('"', "#quot;");
+        writeln!(out, "    n{}[\"{label}\"]", index.index()).unwrap();
+    }
+    for edge in graph.edge_references() {
+        let arrow = match edge.weight().kind {
+            EdgeKind::Link => "-->",
+            EdgeKind::Redirect => "-.->",
+            EdgeKind::Canonical => "==>",
+        };
+        writeln!(
+            out,
+            "    n{} {arrow} n{}",
+            edge.source().index(),
+            edge.target().index()
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// A Graphviz `digraph` with a box around the pages of each host. Redirects are
+/// dashed, and canonical links are bold.
+pub fn to_dot_by_host(graph: &DiGraph<Url, Edge>) -> String {
+    // sorted, so that the same graph always gives the same output
+    let mut hosts = BTreeMap::<_, Vec<_>>::new();
+    for index in graph.node_indices() {
+        hosts
+            .entry(graph[index].host_str())
+            .or_default()
+            .push(index);
+    }
+    let quote = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut out = String::from("digraph {\n");
+    for (host, indices) in hosts {
+        let indent = match host {
+            Some(host) => {
+                let host = quote(host);
+                writeln!(out, "    subgraph \"cluster_{host}\" {{").unwrap();
+                writeln!(out, "        label = \"{host}\"").unwrap();
+                "        "
+            }
+            None => "    ",
+        };
+        for index in indices {
+            let label = quote(graph[index].as_str());
+            writeln!(out, "{indent}{} [ label = \"{label}\" ]", index.index()).unwrap();
+        }
+        if host.is_some() {
+            out.push_str("    }\n");
+        }
+    }
+    for edge in graph.edge_references() {
+        let style = match edge.weight().kind {
+            EdgeKind::Link => "",
+            EdgeKind::Redirect => " [ style = dashed ]",
+            EdgeKind::Canonical => " [ style = bold ]",
+        };
+        writeln!(
+            out,
+            "    {} -> {}{style}",
+            edge.source().index(),
+            edge.target().index()
+        )
+        .unwrap();
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// One `from\tto` line per edge. URLs can't contain tabs or newlines.
+pub fn to_edge_list<E>(graph: &DiGraph<Url, E>) -> String {
+    let mut out = String::new();
+    for edge in graph.edge_references() {
+        writeln!(out, "{}\t{}", graph[edge.source()], graph[edge.target()]).unwrap();
+    }
+    out
+}
+
+/// One `url\tstatus` line per page, sorted by URL, where the status is `ok` or
+/// the error.
+pub fn to_node_list<M>(pages: &HashMap<Url, NodeValue<M>>) -> String {
+    let mut pages = Vec::from_iter(pages);
+    pages.sort_by_key(|(url, _)| *url);
+    let mut out = String::new();
+    for (url, page) in pages {
+        let status = match &page.result {
+            Ok(_) => String::from("ok"),
+            // keep it on one line, in one column
+            Err(e) => e.to_string().replace(char::is_whitespace, " "),
+        };
+        writeln!(out, "{url}\t{status}").unwrap();
+    }
+    out
+}
+
+/// An SQL script that creates and fills `nodes` and `edges` tables, for loading
+/// with `sqlite3 crawl.db < crawl.sql`. Each page is a row of `nodes`, skipped
+/// ones included, with its HTTP `status` (or `NULL`), its `outcome` being `ok`
+/// or the error, and its `content_hash` a hex FNV-1a hash of the body, or
+/// `NULL` if there isn't one.
+/// Each edge's `anchor_text` is the text of its first link that has any, which
+/// needs [`CrawlConfig::link_attributes`](crate::CrawlConfig::link_attributes),
+/// and is `NULL` otherwise.
+///
+/// The CLI's `--format sqlite` runs this through `sqlite3` to write the
+/// database itself, so that nothing links against SQLite.
+pub fn to_sql<M>(result: &CrawlResult<M>) -> String {
+    let mut out = String::from(concat!(
+        "BEGIN;\n",
+        "CREATE TABLE nodes (url TEXT PRIMARY KEY, status INTEGER, outcome TEXT NOT NULL, depth INTEGER NOT NULL, content_hash TEXT, bytes INTEGER);\n",
+        "CREATE TABLE edges (\"from\" TEXT NOT NULL, \"to\" TEXT NOT NULL, kind TEXT NOT NULL, count INTEGER NOT NULL, anchor_text TEXT);\n",
+    ));
+    let mut pages = Vec::from_iter(&result.pages);
+    pages.sort_by_key(|(url, _)| *url);
+    for (url, page) in pages {
+        let (content_hash, bytes) = match &page.result {
+            Ok(response) => (
+                format!("'{:016x}'", fnv1a(response.body.as_bytes())),
+                response.body.len().to_string(),
+            ),
+            Err(_) => (String::from("NULL"), String::from("NULL")),
+        };
+        let status = page.status().map_or_else(
+            || String::from("NULL"),
+            |status| status.as_u16().to_string(),
+        );
+        writeln!(
+            out,
+            "INSERT INTO nodes VALUES ({}, {status}, {}, {}, {content_hash}, {bytes});",
+            sql_string(url.as_str()),
+            sql_string(&outcome(page)),
+            page.depth,
+        )
+        .unwrap();
+    }
+    for edge in result.graph.edge_references() {
+        let anchor_text = edge
+            .weight()
+            .attributes
+            .iter()
+            .find(|attributes| !attributes.text.is_empty())
+            .map_or_else(|| String::from("NULL"), |it| sql_string(&it.text));
+        writeln!(
+            out,
+            "INSERT INTO edges VALUES ({}, {}, '{}', {}, {anchor_text});",
+            sql_string(result.graph[edge.source()].as_str()),
+            sql_string(result.graph[edge.target()].as_str()),
+            kind_name(edge.weight().kind),
+            edge.weight().count,
+        )
+        .unwrap();
+    }
+    out.push_str("COMMIT;\n");
+    out
+}
+
+fn kind_name(kind: EdgeKind) -> &'static str {
+    match kind {
+        EdgeKind::Link => "link",
+        EdgeKind::Redirect => "redirect",
+        EdgeKind::Canonical => "canonical",
+    }
+}
+
+/// `ok`, or the error.
+fn outcome<M>(page: &NodeValue<M>) -> String {
+    match &page.result {
+        Ok(_) => String::from("ok"),
+        Err(e) => e.to_string(),
+    }
+}
+
+/// The graph as `{"nodes": [...], "edges": [...]}`. Each node has its `url`,
+/// `depth`, HTTP `status` (or `null`) and `outcome`, which is `ok` or the error.
+/// Each edge has the indices of the nodes it goes `from` and `to`, its `kind`
+/// and its `count`.
+pub fn to_json<M>(result: &CrawlResult<M>) -> serde_json::Value {
+    let nodes = Vec::from_iter(result.graph.node_weights().map(|url| {
+        let page = &result.pages[url];
+        serde_json::json!({
+            "url": url,
+            "depth": page.depth,
+            "status": page.status().map(|it| it.as_u16()),
+            "outcome": outcome(page),
+        })
+    }));
+    let edges = Vec::from_iter(result.graph.edge_references().map(|edge| {
+        serde_json::json!({
+            "from": edge.source().index(),
+            "to": edge.target().index(),
+            "kind": kind_name(edge.weight().kind),
+            "count": edge.weight().count,
+        })
+    }));
+    serde_json::json!({ "nodes": nodes, "edges": edges })
+}
+
+/// GraphML, for tools like Gephi, with the same attributes as [`to_json`].
+pub fn to_graphml<M>(result: &CrawlResult<M>) -> String {
+    let mut out = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        "\n",
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#,
+        "\n",
+        r#"  <key id="url" for="node" attr.name="url" attr.type="string"/>"#,
+        "\n",
+        r#"  <key id="depth" for="node" attr.name="depth" attr.type="int"/>"#,
+        "\n",
+        r#"  <key id="status" for="node" attr.name="status" attr.type="int"/>"#,
+        "\n",
+        r#"  <key id="outcome" for="node" attr.name="outcome" attr.type="string"/>"#,
+        "\n",
+        r#"  <key id="kind" for="edge" attr.name="kind" attr.type="string"/>"#,
+        "\n",
+        r#"  <key id="count" for="edge" attr.name="count" attr.type="int"/>"#,
+        "\n",
+        r#"  <graph id="crawl" edgedefault="directed">"#,
+        "\n",
+    ));
+    for index in result.graph.node_indices() {
+        let url = &result.graph[index];
+        let page = &result.pages[url];
+        writeln!(out, r#"    <node id="n{}">"#, index.index()).unwrap();
+        writeln!(
+            out,
+            r#"      <data key="url">{}</data>"#,
+            escape_xml(url.as_str())
+        )
+        .unwrap();
+        writeln!(out, r#"      <data key="depth">{}</data>"#, page.depth).unwrap();
+        if let Some(status) = page.status() {
+            let status = status.as_u16();
+            writeln!(out, r#"      <data key="status">{status}</data>"#).unwrap();
+        }
+        writeln!(
+            out,
+            r#"      <data key="outcome">{}</data>"#,
+            escape_xml(&outcome(page))
+        )
+        .unwrap();
+        writeln!(out, "    </node>").unwrap();
+    }
+    for edge in result.graph.edge_references() {
+        writeln!(
+            out,
+            r#"    <edge source="n{}" target="n{}">"#,
+            edge.source().index(),
+            edge.target().index()
+        )
+        .unwrap();
+        writeln!(
+            out,
+            r#"      <data key="kind">{}</data>"#,
+            kind_name(edge.weight().kind)
+        )
+        .unwrap();
+        writeln!(
+            out,
+            r#"      <data key="count">{}</data>"#,
+            edge.weight().count
+        )
+        .unwrap();
+        writeln!(out, "    </edge>").unwrap();
+    }
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
+
+fn sql_string(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Stable across builds, unlike [`std::hash::DefaultHasher`].
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// A `sitemap.xml` of the pages with the same origin as `base` that were
+/// fetched successfully, sorted by URL. Pages that redirected elsewhere are
+/// left out, and `<lastmod>` comes from `Last-Modified`.
+pub fn to_sitemap<M>(result: &CrawlResult<M>, base: &Url) -> String {
+    let mut pages = Vec::from_iter(result.pages.iter().filter(|(url, page)| {
+        url.origin() == base.origin()
+            && page
+                .result
+                .as_ref()
+                .is_ok_and(|response| response.final_url == **url)
+    }));
+    pages.sort_by_key(|(url, _)| *url);
+    let mut out = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        "\n",
+        r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#,
+        "\n",
+    ));
+    for (url, page) in pages {
+        writeln!(out, "  <url>").unwrap();
+        writeln!(out, "    <loc>{}</loc>", escape_xml(url.as_str())).unwrap();
+        if let Some(last_modified) = page.last_modified {
+            writeln!(
+                out,
+                "    <lastmod>{}</lastmod>",
+                w3c_datetime(last_modified)
+            )
+            .unwrap();
+        }
+        writeln!(out, "  </url>").unwrap();
+    }
+    out.push_str("</urlset>\n");
+    out
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\'', "&apos;")
+        .replace('"', "&quot;")
+}
+
+/// Like `2022-06-01T12:30:00Z`.
+fn w3c_datetime(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |it| it.as_secs() as i64);
+    let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
@@ -0,0 +1,489 @@
+use soup::{NodeExt, QueryBuilderExt, Soup};
+use std::collections::HashSet;
+use tracing::warn;
+use url::Url;
+
+use crate::PageResponse;
+
+/// The most links [`links`] and [`anchors`] find in one page, and the default
+/// for [`Extractor::max_links_extracted`].
+pub const MAX_LINKS: usize = 100_000;
+
+/// The targets of every `<a href="...">` in an HTML `body`, in document order,
+/// with relative links resolved against `url`, up to [`MAX_LINKS`] of them.
+/// `href`s that aren't URLs don't count towards that.
+pub fn links(url: &Url, body: &str) -> Vec<Url> {
+    Soup::new(body)
+        .tag("a")
+        .attr_name("href")
+        .find_all()
+        .filter_map(|anchor| resolve(url, &anchor.get("href").expect("Already filtered by href")))
+        .take(MAX_LINKS)
+        .collect()
+}
+
+/// How an `<a>` element linked to its target, for
+/// [`CrawlConfig::link_attributes`](crate::CrawlConfig::link_attributes).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LinkAttributes {
+    /// Each space separated value of `rel`, lowercased, like `nofollow`,
+    /// `sponsored` or `ugc`.
+    pub rel: Vec<String>,
+    /// Like `_blank`.
+    pub target: Option<String>,
+    /// The name of the nearest enclosing `nav`, `header`, `footer`, `main` or
+    /// `aside` element.
+    pub section: Option<String>,
+    /// What the link says, with runs of whitespace collapsed to one space.
+    pub text: String,
+}
+
+/// Like [`links`], along with the attributes of each `<a>`.
+pub fn anchors(url: &Url, body: &str) -> Vec<(Url, LinkAttributes)> {
+    Soup::new(body)
+        .tag("a")
+        .attr_name("href")
+        .find_all()
+        .filter_map(|anchor| {
+            let link = resolve(url, &anchor.get("href").expect("Already filtered by href"))?;
+            let mut section = None;
+            let mut parent = anchor.parent();
+            while let Some(node) = parent {
+                if let name @ ("nav" | "header" | "footer" | "main" | "aside") = node.name() {
+                    section = Some(name.to_owned());
+                    break;
+                }
+                parent = node.parent();
+            }
+            let attributes = LinkAttributes {
+                rel: Vec::from_iter(
+                    anchor
+                        .get("rel")
+                        .unwrap_or_default()
+                        .split_ascii_whitespace()
+                        .map(str::to_ascii_lowercase),
+                ),
+                target: anchor.get("target"),
+                section,
+                text: Vec::from_iter(anchor.text().split_whitespace()).join(" "),
+            };
+            Some((link, attributes))
+        })
+        .take(MAX_LINKS)
+        .collect()
+}
+
+/// The `href`s of the `<a>` elements in an HTML `body` that [`links`] leaves
+/// out because they aren't URLs, even relative to `url`, in document order.
+pub fn malformed_hrefs(url: &Url, body: &str) -> Vec<String> {
+    Soup::new(body)
+        .tag("a")
+        .attr_name("href")
+        .find_all()
+        .filter_map(|anchor| anchor.get("href"))
+        .filter(|href| try_resolve(url, href).is_err())
+        .collect()
+}
+
+/// Malformed links are logged, and left out.
+fn resolve(base: &Url, href: &str) -> Option<Url> {
+    try_resolve(base, href)
+        .inspect_err(|e| warn!("Ignoring {href:?} relative to {base}: {e}"))
+        .ok()
+}
+
+fn try_resolve(base: &Url, href: &str) -> Result<Url, url::ParseError> {
+    // protocol-relative, like `//cdn.example.com/app.js`, which joining only
+    // gets right if `base` can be a base
+    if let Some(rest) = href.trim_start().strip_prefix("//") {
+        return format!("{}://{rest}", base.scheme()).parse();
+    }
+    match href.parse::<Url>() {
+        Err(url::ParseError::RelativeUrlWithoutBase) => base.join(href),
+        res => res,
+    }
+}
+
+/// Builds a `get_children` for HTML pages, like [`links`] with some common
+/// tweaks.
+#[derive(Debug, Clone)]
+pub struct Extractor {
+    follow_nofollow: bool,
+    include_iframes: bool,
+    include_srcset: bool,
+    include_forms: bool,
+    strip_fragments: bool,
+    respect_base_href: bool,
+    allowed_schemes: Option<Vec<String>>,
+    max_links_extracted: usize,
+}
+
+impl Default for Extractor {
+    fn default() -> Self {
+        Self {
+            follow_nofollow: true,
+            include_iframes: false,
+            include_srcset: false,
+            include_forms: false,
+            strip_fragments: false,
+            respect_base_href: false,
+            allowed_schemes: None,
+            max_links_extracted: MAX_LINKS,
+        }
+    }
+}
+
+impl Extractor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to keep links with `rel="nofollow"`. Defaults to true.
+    pub fn follow_nofollow(mut self, follow: bool) -> Self {
+        self.follow_nofollow = follow;
+        self
+    }
+
+    /// Whether to include the `src` of each `<iframe>`.
+    pub fn include_iframes(mut self, include: bool) -> Self {
+        self.include_iframes = include;
+        self
+    }
+
+    /// Whether to include every candidate in the `srcset` of each `<img>` and
+    /// `<source>`.
+    pub fn include_srcset(mut self, include: bool) -> Self {
+        self.include_srcset = include;
+        self
+    }
+
+    /// Whether to include the URL that submitting each `GET` `<form>` with its
+    /// default values would load. See [`forms`].
+    pub fn include_forms(mut self, include: bool) -> Self {
+        self.include_forms = include;
+        self
+    }
+
+    pub fn strip_fragments(mut self, strip: bool) -> Self {
+        self.strip_fragments = strip;
+        self
+    }
+
+    /// Whether to resolve relative links against `<base href="...">` rather
+    /// than the page's URL.
+    pub fn respect_base_href(mut self, respect: bool) -> Self {
+        self.respect_base_href = respect;
+        self
+    }
+
+    /// Only keep links with these schemes. By default, every scheme is kept.
+    pub fn allowed_schemes<S: Into<String>>(
+        mut self,
+        schemes: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.allowed_schemes = Some(schemes.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Stop looking for links after this many, so that a page with millions of
+    /// anchors doesn't collect millions of URLs. The page is still parsed in
+    /// full. Defaults to 100,000.
+    pub fn max_links_extracted(mut self, max: usize) -> Self {
+        self.max_links_extracted = max;
+        self
+    }
+
+    /// The links in an HTML `body`, in document order, with those from
+    /// iframes after those from anchors, then those from `srcset`s, then those
+    /// from forms.
+    pub fn links(&self, url: &Url, body: &str) -> Vec<Url> {
+        let soup = Soup::new(body);
+        let base = match self.respect_base_href {
+            true => soup
+                .tag("base")
+                .attr_name("href")
+                .find()
+                .and_then(|base| url.join(&base.get("href")?).ok()),
+            false => None,
+        };
+        let base = base.as_ref().unwrap_or(url);
+        let anchors = soup.tag("a").attr_name("href").find_all().filter(|anchor| {
+            self.follow_nofollow
+                || !anchor.get("rel").is_some_and(|rel| {
+                    rel.split_whitespace()
+                        .any(|it| it.eq_ignore_ascii_case("nofollow"))
+                })
+        });
+        let max = self.max_links_extracted;
+        // only the links that are kept count towards `max`
+        let keep = |mut link: Url| {
+            let allowed = self
+                .allowed_schemes
+                .as_ref()
+                .is_none_or(|schemes| schemes.iter().any(|it| it == link.scheme()));
+            if self.strip_fragments {
+                link.set_fragment(None);
+            }
+            allowed.then_some(link)
+        };
+        let resolved = |href: String| resolve(base, &href);
+        let mut links = Vec::from_iter(
+            anchors
+                .filter_map(|anchor| anchor.get("href"))
+                .filter_map(resolved)
+                .filter_map(keep)
+                .take(max),
+        );
+        if self.include_iframes {
+            let left = max - links.len();
+            links.extend(
+                soup.tag("iframe")
+                    .attr_name("src")
+                    .find_all()
+                    .filter_map(|iframe| iframe.get("src"))
+                    .filter_map(resolved)
+                    .filter_map(keep)
+                    .take(left),
+            );
+        }
+        if self.include_srcset {
+            'tags: for tag in ["img", "source"] {
+                for element in soup.tag(tag).attr_name("srcset").find_all() {
+                    if links.len() >= max {
+                        break 'tags;
+                    }
+                    let attr = element.get("srcset").expect("Already filtered by srcset");
+                    let left = max - links.len();
+                    links.extend(
+                        srcset(&attr)
+                            .into_iter()
+                            .filter_map(|href| resolve(base, href))
+                            .filter_map(keep)
+                            .take(left),
+                    );
+                }
+            }
+        }
+        if self.include_forms {
+            let left = max - links.len();
+            links.extend(
+                form_targets(base, &soup)
+                    .into_iter()
+                    .filter_map(keep)
+                    .take(left),
+            );
+        }
+        links
+    }
+
+    /// A `get_children` for [`build_graph`](crate::build_graph) and friends.
+    pub fn build(
+        self,
+    ) -> impl Fn(&PageResponse, usize) -> Option<HashSet<Url>> + Clone + Send + Sync + 'static {
+        move |response, _depth| {
+            let links = self.links(&response.final_url, &response.body);
+            Some(links.into_iter().collect())
+        }
+    }
+}
+
+/// The target of a `<link rel="canonical" href="...">` in an HTML `body`.
+pub fn canonical(url: &Url, body: &str) -> Option<Url> {
+    // avoid parsing the page again in the common case
+    if !body.contains("canonical") {
+        return None;
+    }
+    let href = Soup::new(body)
+        .tag("link")
+        .attr("rel", "canonical")
+        .find()?
+        .get("href")?;
+    url.join(&href).ok()
+}
+
+/// The URL that submitting each `<form method="get">` in an HTML `body` would
+/// load, with the query built from its fields' default values, to find search
+/// and filter pages that nothing links to. Checkboxes and radio buttons count
+/// only if they're `checked`, and buttons and file inputs are left out.
+pub fn forms(url: &Url, body: &str) -> Vec<Url> {
+    form_targets(url, &Soup::new(body))
+}
+
+fn form_targets(base: &Url, soup: &impl QueryBuilderExt) -> Vec<Url> {
+    let mut targets = Vec::new();
+    for form in soup.tag("form").find_all() {
+        let method = form.get("method").unwrap_or_default();
+        if !(method.is_empty() || method.eq_ignore_ascii_case("get")) {
+            continue;
+        }
+        let action = form.get("action").unwrap_or_default();
+        let Some(mut target) = (match action.trim() {
+            "" => Some(base.clone()),
+            action => resolve(base, action),
+        }) else {
+            continue;
+        };
+        let mut fields = Vec::new();
+        for field in form.tag(true).find_all() {
+            let Some(name) = field.get("name").filter(|it| !it.is_empty()) else {
+                continue;
+            };
+            let value = match field.name() {
+                "input" => {
+                    let kind = field.get("type").unwrap_or_default().to_ascii_lowercase();
+                    match kind.as_str() {
+                        "submit" | "button" | "image" | "reset" | "file" => continue,
+                        "checkbox" | "radio" if field.get("checked").is_none() => continue,
+                        "checkbox" | "radio" => field.get("value").unwrap_or_else(|| "on".into()),
+                        _ => field.get("value").unwrap_or_default(),
+                    }
+                }
+                "select" => {
+                    let options = Vec::from_iter(field.tag("option").find_all());
+                    let Some(option) = options
+                        .iter()
+                        .find(|it| it.get("selected").is_some())
+                        .or(options.first())
+                    else {
+                        continue;
+                    };
+                    option.get("value").unwrap_or_else(|| option.text())
+                }
+                "textarea" => field.text(),
+                _ => continue,
+            };
+            fields.push((name, value));
+        }
+        target.set_fragment(None);
+        match fields.is_empty() {
+            true => target.set_query(None),
+            false => {
+                target.query_pairs_mut().clear().extend_pairs(fields);
+            }
+        }
+        targets.push(target);
+    }
+    targets
+}
+
+/// The URLs of the candidates in a `srcset` attribute like
+/// `small.jpg 640w, large.jpg 2x`, without their descriptors. URLs may contain
+/// commas, but not at their start or end.
+pub fn srcset(attr: &str) -> Vec<&str> {
+    let mut urls = Vec::new();
+    let mut rest = attr;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == ',');
+        if rest.is_empty() {
+            return urls;
+        }
+        let end = rest
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        let (url, after) = rest.split_at(end);
+        let trimmed = url.trim_end_matches(',');
+        urls.push(trimmed);
+        rest = after;
+        if trimmed.len() != url.len() {
+            // a trailing comma ends the candidate, with no descriptors
+            continue;
+        }
+        // skip the descriptors, up to a comma that isn't in parentheses
+        let mut depth = 0;
+        let end = rest
+            .find(|c: char| {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    ',' if depth <= 0 => return true,
+                    _ => {}
+                }
+                false
+            })
+            .unwrap_or(rest.len());
+        rest = &rest[end..];
+    }
+}
+
+/// The target and relation of each link in an HTTP `Link` header like
+/// `<https://example.com/?page=2>; rel="next"`, with targets left unresolved.
+/// Links with several space-separated relations appear once for each, and
+/// links without a `rel` are left out.
+pub fn link_header(value: &str) -> Vec<(&str, &str)> {
+    let mut links = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let target = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+        // the parameters run up to a comma that isn't in quotes
+        let mut quoted = false;
+        let end = rest
+            .find(|c: char| {
+                match c {
+                    '"' => quoted = !quoted,
+                    ',' if !quoted => return true,
+                    _ => {}
+                }
+                false
+            })
+            .unwrap_or(rest.len());
+        let (params, after) = rest.split_at(end);
+        rest = after;
+        let rel = params.split(';').find_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            name.trim()
+                .eq_ignore_ascii_case("rel")
+                .then(|| value.trim().trim_matches('"'))
+        });
+        for rel in rel.into_iter().flat_map(str::split_ascii_whitespace) {
+            links.push((target.trim(), rel));
+        }
+    }
+    links
+}
+
+/// Root-relative paths in string literals inside `<script>` elements of an
+/// HTML `body`, and the same-origin scripts it loads. See [`script_paths`].
+pub fn script_links(url: &Url, body: &str) -> Vec<Url> {
+    let soup = Soup::new(body);
+    let mut links = Vec::new();
+    for script in soup.tag("script").find_all() {
+        match script.get("src") {
+            Some(src) => links.extend(url.join(&src).ok().filter(|it| it.origin() == url.origin())),
+            None => links.extend(script_paths(url, &script.text())),
+        }
+    }
+    links
+}
+
+/// Guesses at links in JavaScript source: string literals that look like a
+/// root-relative path, like `"/api/items"`. Anything built up at runtime is
+/// missed, and strings that only look like paths (such as regular expressions
+/// or MIME types) become links that may not exist.
+pub fn script_paths(url: &Url, source: &str) -> Vec<Url> {
+    let mut paths = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find(['"', '\'', '`']) {
+        let quote = rest[start..].chars().next().expect("just found");
+        rest = &rest[start + 1..];
+        let Some(end) = rest.find(quote) else {
+            break;
+        };
+        let literal = &rest[..end];
+        rest = &rest[end + 1..];
+        let looks_like_path = literal.len() > 1
+            && literal.starts_with('/')
+            && !literal.starts_with("//")
+            && literal
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-._~/%?=&+".contains(c));
+        if looks_like_path {
+            paths.extend(url.join(literal).ok());
+        }
+    }
+    paths
+}
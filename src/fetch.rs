@@ -0,0 +1,296 @@
+use encoding_rs::{Encoding, UTF_8};
+use mime::Mime;
+use reqwest::{
+    header::{HeaderMap, ACCEPT_LANGUAGE, CONTENT_TYPE, LAST_MODIFIED, LINK, LOCATION, USER_AGENT},
+    Client, Response, StatusCode,
+};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+use tokio::time::Instant;
+use tracing::debug;
+use url::Url;
+
+use crate::{extract, throttle::Throttle, CrawlConfig, FetchError, SkipReason};
+
+pub(crate) struct Fetched {
+    pub body: String,
+    /// Of the final response, if there was one.
+    pub status: Option<StatusCode>,
+    pub content_type: Option<String>,
+    /// From the `Last-Modified` header.
+    pub last_modified: Option<SystemTime>,
+    /// The `rel="next"` and `rel="prev"` targets of the `Link` headers.
+    pub pagination: Vec<Url>,
+    /// Each URL that was redirected to on the way to `body`.
+    pub redirects: Vec<Url>,
+}
+
+/// A response that [`CrawlConfig::should_retry`] is deciding about.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct FetchAttempt<'a> {
+    pub url: &'a Url,
+    pub status: StatusCode,
+    pub headers: &'a HeaderMap,
+    /// Starting from 1.
+    pub attempt: usize,
+}
+
+/// Responses with a status outside [`CrawlConfig::success_statuses`] are
+/// [`FetchError::Status`].
+pub(crate) async fn get_webpage(
+    config: &CrawlConfig,
+    client: &Client,
+    throttle: Option<&Throttle>,
+    requests: &AtomicUsize,
+    url: &Url,
+) -> Result<Fetched, FetchError> {
+    let fetched = get_any_webpage(config, client, throttle, requests, url).await?;
+    match fetched.status {
+        Some(status) if !config.success_statuses.contains(&status.as_u16()) => {
+            Err(FetchError::Status(status))
+        }
+        _ => Ok(fetched),
+    }
+}
+
+/// Like [`get_webpage`], but responses with other statuses are read too, for
+/// custom error pages, unless their bodies can't be, when they're
+/// [`FetchError::Status`].
+pub(crate) async fn get_any_webpage(
+    config: &CrawlConfig,
+    client: &Client,
+    throttle: Option<&Throttle>,
+    requests: &AtomicUsize,
+    url: &Url,
+) -> Result<Fetched, FetchError> {
+    if config.read_files && url.scheme() == "file" {
+        return read_file(config, url).await;
+    }
+    let mut url = url.clone();
+    let mut redirects = Vec::new();
+    for _ in 0..=config.max_redirects {
+        let response = send(config, client, requests, &url).await?;
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .and_then(|location| url.join(location).ok());
+        match location {
+            Some(location) if response.status().is_redirection() => {
+                redirects.push(location.clone());
+                url = location;
+            }
+            _ if !config
+                .success_statuses
+                .contains(&response.status().as_u16()) =>
+            {
+                let status = response.status();
+                return match read_body(response, throttle, config).await {
+                    Ok(fetched) => Ok(Fetched {
+                        redirects,
+                        ..fetched
+                    }),
+                    Err(_) => Err(FetchError::Status(status)),
+                };
+            }
+            _ => {
+                return read_body(response, throttle, config)
+                    .await
+                    .map(|fetched| Fetched {
+                        redirects,
+                        ..fetched
+                    })
+            }
+        }
+    }
+    Err(FetchError::RedirectLimitExceeded {
+        limit: config.max_redirects,
+    })
+}
+
+async fn read_file(config: &CrawlConfig, url: &Url) -> Result<Fetched, FetchError> {
+    let mut path = url
+        .to_file_path()
+        .map_err(|()| FetchError::Other(format!("{url} isn't a local path")))?;
+    if url.path().ends_with('/') {
+        path.push("index.html");
+    }
+    let error = |e: std::io::Error| FetchError::Other(format!("{}: {e}", path.display()));
+    let metadata = tokio::fs::metadata(&path).await.map_err(error)?;
+    if let Some(limit) = config.max_body_bytes {
+        if metadata.len() > limit {
+            return Err(FetchError::Skipped(SkipReason::BodyTooLarge {
+                size: Some(metadata.len()),
+                limit,
+            }));
+        }
+    }
+    let bytes = tokio::fs::read(&path).await.map_err(error)?;
+    let html = path
+        .extension()
+        .is_some_and(|it| it.eq_ignore_ascii_case("html") || it.eq_ignore_ascii_case("htm"));
+    Ok(Fetched {
+        body: String::from_utf8_lossy(&bytes).into_owned(),
+        status: None,
+        content_type: html.then(|| mime::TEXT_HTML.to_string()),
+        last_modified: metadata.modified().ok(),
+        pagination: Vec::new(),
+        redirects: Vec::new(),
+    })
+}
+
+/// Sends a request for `url`, again for as long as the config wants it retried.
+async fn send(
+    config: &CrawlConfig,
+    client: &Client,
+    requests: &AtomicUsize,
+    url: &Url,
+) -> Result<Response, FetchError> {
+    let mut attempt = 1;
+    loop {
+        let mut request = config.client_for(url, client).get(url.clone());
+        // clients that weren't built from `config` don't send these themselves
+        if let Some(user_agent) = &config.user_agent {
+            request = request.header(USER_AGENT, user_agent);
+        }
+        if let Some(accept_language) = &config.accept_language {
+            request = request.header(ACCEPT_LANGUAGE, accept_language);
+        }
+        if let Some((username, password)) =
+            url.host_str().and_then(|host| config.basic_auth.get(host))
+        {
+            request = request.basic_auth(username, Some(password));
+        }
+        if let Some(hook) = &config.request_hook {
+            request = hook(url, request);
+        }
+        requests.fetch_add(1, Ordering::Relaxed);
+        let response = match request.send().await {
+            Err(e) if attempt <= config.max_retries && (e.is_connect() || e.is_timeout()) => {
+                debug!("Retrying {url} after {e}");
+                tokio::time::sleep(backoff(config.retry_base_delay, attempt)).await;
+                attempt += 1;
+                continue;
+            }
+            response => response?,
+        };
+        let status = response.status();
+        let retry = attempt <= config.max_retries
+            && match &config.should_retry {
+                Some(should_retry) => should_retry(&FetchAttempt {
+                    url,
+                    status,
+                    headers: response.headers(),
+                    attempt,
+                }),
+                None => status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS,
+            };
+        if !retry {
+            return Ok(response);
+        }
+        tokio::time::sleep(backoff(config.retry_base_delay, attempt)).await;
+        attempt += 1;
+    }
+}
+
+/// How long to wait after the `attempt`th request, from 1.
+fn backoff(base: Duration, attempt: usize) -> Duration {
+    let delay = base.saturating_mul(1 << (attempt - 1).min(16));
+    let jitter = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+    delay.mul_f64(1.0 + jitter / 2.0)
+}
+
+/// Like [`Response::text`], but whatever arrived before an error is kept in
+/// [`FetchError::PartialBody`], or [`FetchError::BodyTimeout`].
+async fn read_body(
+    mut response: Response,
+    throttle: Option<&Throttle>,
+    config: &CrawlConfig,
+) -> Result<Fetched, FetchError> {
+    let limit = config.max_body_bytes;
+    let mut deadline = config
+        .body_read_timeout
+        .map(|timeout| Instant::now() + timeout);
+    let too_large = |size| {
+        FetchError::Skipped(SkipReason::BodyTooLarge {
+            size,
+            limit: limit.unwrap_or_default(),
+        })
+    };
+    if let (Some(limit), Some(size)) = (limit, response.content_length()) {
+        if size > limit {
+            return Err(too_large(Some(size)));
+        }
+    }
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+    let last_modified = response
+        .headers()
+        .get(LAST_MODIFIED)
+        .and_then(|value| httpdate::parse_http_date(value.to_str().ok()?).ok());
+    let pagination = Vec::from_iter(
+        response
+            .headers()
+            .get_all(LINK)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(extract::link_header)
+            .filter(|(_, rel)| rel.eq_ignore_ascii_case("next") || rel.eq_ignore_ascii_case("prev"))
+            .filter_map(|(target, _)| response.url().join(target).ok()),
+    );
+    let encoding = content_type
+        .as_ref()
+        .and_then(|value| value.parse::<Mime>().ok())
+        .and_then(|mime| Encoding::for_label(mime.get_param("charset")?.as_str().as_bytes()))
+        .unwrap_or(UTF_8);
+    let mut bytes = Vec::new();
+    loop {
+        let chunk = match deadline {
+            Some(deadline) => match tokio::time::timeout_at(deadline, response.chunk()).await {
+                Ok(chunk) => chunk,
+                Err(_) => return Err(FetchError::BodyTimeout { bytes }),
+            },
+            None => response.chunk().await,
+        };
+        match chunk {
+            Ok(Some(chunk)) => {
+                if let Some(throttle) = throttle {
+                    let start = Instant::now();
+                    throttle.consume(chunk.len()).await;
+                    // being throttled isn't the server's fault
+                    if let Some(deadline) = &mut deadline {
+                        *deadline += start.elapsed();
+                    }
+                }
+                bytes.extend_from_slice(&chunk);
+                // the server didn't say how big it was, or lied
+                if limit.is_some_and(|limit| bytes.len() as u64 > limit) {
+                    return Err(too_large(None));
+                }
+            }
+            Ok(None) => break,
+            // what arrived can't be read either
+            Err(e) if e.is_decode() => return Err(e.into()),
+            Err(e) => {
+                return Err(FetchError::PartialBody {
+                    error: e.to_string(),
+                    bytes,
+                })
+            }
+        }
+    }
+    Ok(Fetched {
+        body: encoding.decode(&bytes).0.into_owned(),
+        status: Some(response.status()),
+        content_type,
+        last_modified,
+        pagination,
+        redirects: Vec::new(),
+    })
+}
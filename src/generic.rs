@@ -0,0 +1,161 @@
+use crate::{CrawlConfig, Strategy};
+use futures::{stream::FuturesUnordered, Future, StreamExt};
+use petgraph::graph::DiGraph;
+use std::{
+    collections::{hash_map::Entry, HashMap, VecDeque},
+    hash::Hash,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// Walks the graph from `roots`, calling `neighbors` once for every node
+/// found. Nothing here is specific to the web, so this can walk a filesystem or a
+/// dependency graph. Each edge is added at most once.
+///
+/// This is the same traversal [`build_graph`](crate::build_graph) does, so
+/// [`CrawlConfig::strategy`], [`max_concurrency`](CrawlConfig::max_concurrency),
+/// [`max_depth`](CrawlConfig::max_depth), [`max_pages`](CrawlConfig::max_pages),
+/// [`max_duration`](CrawlConfig::max_duration),
+/// [`idle_timeout`](CrawlConfig::idle_timeout) and
+/// [`cancellation`](CrawlConfig::cancellation) apply, with nodes for pages.
+/// Nodes that weren't expanded because of them are still in the graph. The
+/// rest of the config is about fetching, and is ignored.
+pub async fn build_graph_generic<N, F, Fut>(
+    config: &CrawlConfig,
+    roots: impl IntoIterator<Item = N>,
+    neighbors: F,
+) -> DiGraph<N, ()>
+where
+    N: Hash + Eq + Clone,
+    F: Fn(N) -> Fut,
+    Fut: Future<Output = Vec<N>>,
+{
+    let deadline = config
+        .max_duration
+        .map(|duration| Instant::now() + duration);
+    let cancellation = config.cancellation.child_token();
+    let mut graph = DiGraph::new();
+    let mut indices = HashMap::new();
+    let mut start = Vec::new();
+    for root in roots {
+        if let Entry::Vacant(entry) = indices.entry(root.clone()) {
+            entry.insert(graph.add_node(root.clone()));
+            start.push((root, 0));
+        }
+    }
+    let state = Mutex::new((graph, indices));
+    let visited = AtomicUsize::new(0);
+    let visit = |node: N, depth| {
+        let (cancellation, neighbors) = (&cancellation, &neighbors);
+        let (state, visited) = (&state, &visited);
+        async move {
+            let spend = |visited| match config.max_pages {
+                Some(max) if visited >= max => None,
+                _ => Some(visited + 1),
+            };
+            let budget = visited.fetch_update(Ordering::Relaxed, Ordering::Relaxed, spend);
+            if cancellation.is_cancelled()
+                || config.max_depth.is_some_and(|max| depth >= max)
+                || budget.is_err()
+            {
+                return (Vec::new(), depth);
+            }
+            let children = tokio::select! {
+                children = neighbors(node.clone()) => children,
+                _ = cancellation.cancelled() => return (Vec::new(), depth),
+            };
+            let mut state = state.lock().unwrap();
+            let (graph, indices) = &mut *state;
+            let from = indices[&node];
+            let mut found = Vec::new();
+            for child in children {
+                let to = match indices.entry(child) {
+                    Entry::Occupied(entry) => *entry.get(),
+                    Entry::Vacant(entry) => {
+                        let child = entry.key().clone();
+                        let index = *entry.insert(graph.add_node(child.clone()));
+                        found.push(child);
+                        index
+                    }
+                };
+                graph.update_edge(from, to, ());
+            }
+            (found, depth)
+        }
+    };
+    tokio::select! {
+        _ = traverse(config, &cancellation, start, visit) => {}
+        _ = cancel_at(&cancellation, deadline) => unreachable!("never returns"),
+    }
+    state.into_inner().unwrap().0
+}
+
+/// Calls `visit` for each node from `start`, and then for the children it
+/// returns, a level below the depth it returns, in the order
+/// [`CrawlConfig::strategy`] says, until there are none left. `visit` decides
+/// what's been seen before.
+pub(crate) async fn traverse<N, Fut>(
+    config: &CrawlConfig,
+    cancellation: &CancellationToken,
+    start: Vec<(N, usize)>,
+    visit: impl Fn(N, usize) -> Fut,
+) where
+    Fut: Future<Output = (Vec<N>, usize)>,
+{
+    let mut pending = VecDeque::from(start);
+    if config.strategy == Strategy::DepthFirst {
+        pending.make_contiguous().reverse();
+    }
+    let mut in_flight = FuturesUnordered::new();
+    loop {
+        let max_concurrency = match config.strategy {
+            Strategy::Concurrent => usize::MAX,
+            _ => config.max_concurrency.max(1),
+        };
+        while in_flight.len() < max_concurrency {
+            let next = match config.strategy {
+                Strategy::DepthFirst => pending.pop_back(),
+                _ => pending.pop_front(),
+            };
+            let Some((node, depth)) = next else {
+                break;
+            };
+            in_flight.push(visit(node, depth));
+        }
+        let next = match config.idle_timeout {
+            Some(idle) => match tokio::time::timeout(idle, in_flight.next()).await {
+                Ok(next) => next,
+                Err(_) => {
+                    warn!("Nothing finished for {idle:?}, stopping the crawl");
+                    cancellation.cancel();
+                    in_flight.next().await
+                }
+            },
+            None => in_flight.next().await,
+        };
+        let Some((mut children, depth)) = next else {
+            break;
+        };
+        if config.strategy == Strategy::DepthFirst {
+            // so that the first child is on top of the stack
+            children.reverse();
+        }
+        pending.extend(children.into_iter().map(|child| (child, depth + 1)));
+    }
+}
+
+/// Cancels the crawl at `deadline`, for [`CrawlConfig::max_duration`],
+/// never returning.
+pub(crate) async fn cancel_at(cancellation: &CancellationToken, deadline: Option<Instant>) {
+    if let Some(deadline) = deadline {
+        tokio::time::sleep_until(deadline).await;
+        warn!("Reached the maximum duration, stopping the crawl");
+        cancellation.cancel();
+    }
+    std::future::pending().await
+}
@@ -0,0 +1,148 @@
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+    time::SystemTime,
+};
+use tracing::warn;
+use url::Url;
+
+use crate::{EdgeKind, FetchError, NodeValue, PageResponse};
+
+/// An append-only file with a line of JSON for every visited page, so that an
+/// interrupted crawl can pick up where it left off.
+pub(crate) struct Journal {
+    file: Mutex<File>,
+}
+
+/// Some pages and the edges found from them. Later records of the same page
+/// replace earlier ones.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Record {
+    /// A page and the pages it was redirected through, which share a value.
+    pub urls: Vec<Url>,
+    pub page: Page,
+    pub edges: Vec<(Url, Url, EdgeKind)>,
+}
+
+/// A [`NodeValue`] without its metadata, which is recomputed when replayed, and
+/// with its [`PageResponse`] spread out.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Page {
+    result: Result<String, FetchError>,
+    /// Missing from journals written before it was recorded.
+    #[serde(default)]
+    error_body: Option<String>,
+    content_type: Option<String>,
+    last_modified: Option<SystemTime>,
+    depth: usize,
+    /// Older journals only have this if the page was redirected.
+    final_url: Option<Url>,
+    /// Missing from journals written before it was recorded.
+    #[serde(default)]
+    pagination: Vec<Url>,
+    /// Missing from journals written before it was recorded.
+    #[serde(default)]
+    status: Option<u16>,
+    fetched_at: SystemTime,
+}
+
+impl Journal {
+    /// Returns the records already in the file at `path`, creating it if needed.
+    pub fn open(path: &Path) -> io::Result<(Self, Vec<Record>)> {
+        let mut records = Vec::new();
+        match File::open(path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    // the last line is cut short if we stopped mid-write
+                    match serde_json::from_str(&line?) {
+                        Ok(record) => records.push(record),
+                        Err(e) => warn!("Ignoring bad journal record: {e}"),
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok((
+            Self {
+                file: Mutex::new(file),
+            },
+            records,
+        ))
+    }
+
+    /// Failures are logged rather than stopping the crawl.
+    pub fn write(&self, record: &Record) {
+        let mut line = serde_json::to_string(record).expect("records are always serializable");
+        line.push('\n');
+        if let Err(e) = self.file.lock().unwrap().write_all(line.as_bytes()) {
+            warn!("Couldn't write to the journal: {e}");
+        }
+    }
+}
+
+/// Replaces whatever is at `path` with `records`, in the journal's format, by
+/// writing them to a temporary file and renaming it.
+pub(crate) fn write_snapshot(path: &Path, records: &[Record]) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let mut file = BufWriter::new(File::create(&temporary)?);
+    for record in records {
+        serde_json::to_writer(&mut file, record).expect("records are always serializable");
+        file.write_all(b"\n")?;
+    }
+    file.into_inner()?.sync_all()?;
+    fs::rename(temporary, path)
+}
+
+impl Page {
+    pub fn new<M>(value: &NodeValue<M>) -> Self {
+        let response = value.response();
+        Self {
+            result: value.result.clone().map(|it| it.body),
+            error_body: value.error_response.as_ref().map(|it| it.body.clone()),
+            content_type: response.and_then(|it| it.content_type.clone()),
+            last_modified: value.last_modified,
+            depth: value.depth,
+            final_url: response.map(|it| it.final_url.clone()),
+            pagination: value.pagination.clone(),
+            status: value.status().map(|it| it.as_u16()),
+            fetched_at: value.fetched_at,
+        }
+    }
+
+    /// `classify` is given the URL the body came from.
+    pub fn into_value<M>(
+        self,
+        url: &Url,
+        classify: impl Fn(&Url, &str) -> Option<M>,
+    ) -> NodeValue<M> {
+        let final_url = self.final_url.unwrap_or_else(|| url.clone());
+        let status = self.status.and_then(|it| StatusCode::from_u16(it).ok());
+        let response = |body| PageResponse {
+            status,
+            final_url: final_url.clone(),
+            content_type: self.content_type.clone(),
+            body,
+        };
+        NodeValue {
+            metadata: self
+                .result
+                .as_ref()
+                .ok()
+                .and_then(|s| classify(&final_url, s)),
+            result: self.result.map(response),
+            error_response: self.error_body.map(response),
+            last_modified: self.last_modified,
+            depth: self.depth,
+            pagination: self.pagination,
+            fetched_at: self.fetched_at,
+            discovered_as: Vec::new(),
+        }
+    }
+}
@@ -1,102 +1,1551 @@
-use async_recursion::async_recursion;
-use futures::future::join_all;
-use petgraph::graph::DiGraph;
-use reqwest::Client;
-use std::collections::{HashMap, HashSet};
-use tokio::sync::{Mutex, RwLock};
-use tracing::{info, instrument};
-use url::Url;
+use fetch::{get_any_webpage, get_webpage, Fetched};
+use futures::{future, future::BoxFuture, stream, FutureExt, Stream, StreamExt};
+use journal::{write_snapshot, Journal, Page, Record};
+use mime::Mime;
+use mirror::Mirror;
+use normalize::Normalizer;
+use petgraph::graph::{DiGraph, NodeIndex};
+use reqwest::{Client, StatusCode};
+use robots::Robots;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+use throttle::Throttle;
+use tokio::sync::{Mutex, Notify, RwLock, Semaphore};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, instrument, warn};
+use url::{Origin, Url};
 
-async fn get_webpage(client: &Client, url: &Url) -> Result<String, reqwest::Error> {
-    client.get(url.clone()).send().await?.text().await
+pub mod analysis;
+mod config;
+mod context;
+mod error;
+mod event;
+pub mod export;
+pub mod extract;
+mod fetch;
+mod generic;
+mod journal;
+mod mirror;
+mod normalize;
+mod pause;
+mod robots;
+mod throttle;
+
+pub use config::{
+    CheckpointInterval, ContentExtractor, CrawlConfig, Login, Normalize, OnFetch, PreprocessBody,
+    Profile, RenderFallback, RequestHook, Scope, ShouldKeep, ShouldRetry, Strategy,
+};
+pub use context::CrawlContext;
+use context::{NextFetch, RobotsCache};
+pub use error::{FetchError, SkipReason};
+pub use event::{collect_graph, CrawlEvent, CrawlStats};
+pub use extract::LinkAttributes;
+pub use fetch::FetchAttempt;
+pub use generic::build_graph_generic;
+use generic::{cancel_at, traverse};
+pub use pause::PauseToken;
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct CrawlResult<M = ()> {
+    pub graph: DiGraph<Url, Edge>,
+    pub pages: HashMap<Url, NodeValue<M>>,
+    /// The first of [`CrawlResult::seed_indices`].
+    pub root_index: NodeIndex,
+    /// One per seed, in the order they were given.
+    pub seed_indices: Vec<NodeIndex>,
+    /// Pages that were discovered but never fetched, because the crawl was cut
+    /// short.
+    pub frontier: HashSet<Url>,
+    pub stats: CrawlStats,
+    normalizer: Normalizer,
+}
+
+impl<M> CrawlResult<M> {
+    pub fn into_parts(self) -> (DiGraph<Url, Edge>, HashMap<Url, NodeValue<M>>) {
+        (self.graph, self.pages)
+    }
+
+    /// Looks up a page the way the crawl would have named it, so `url` may have
+    /// a fragment, or a trailing slash the crawled page didn't (or vice versa).
+    pub fn get(&self, url: &Url) -> Option<&NodeValue<M>> {
+        self.get_key_value(url).map(|(_, value)| value)
+    }
+
+    /// Like [`CrawlResult::get`], but also gives the name the crawl used.
+    fn get_key_value(&self, url: &Url) -> Option<(&Url, &NodeValue<M>)> {
+        let url = self.normalizer.normalize(url.clone());
+        self.pages.get_key_value(&url).or_else(|| {
+            let mut other = url.clone();
+            match url.path().strip_suffix('/') {
+                Some("") => return None,
+                Some(path) => other.set_path(path),
+                None => other.set_path(&format!("{}/", url.path())),
+            }
+            self.pages.get_key_value(&other)
+        })
+    }
+
+    /// The page's node in [`CrawlResult::graph`], looked up like
+    /// [`CrawlResult::get`].
+    pub fn node_by_url(&self, url: &Url) -> Option<NodeIndex> {
+        let (url, _) = self.get_key_value(url)?;
+        self.graph.node_indices().find(|it| &self.graph[*it] == url)
+    }
+
+    /// The body of the page at `index`, if it was fetched successfully.
+    pub fn body_of(&self, index: NodeIndex) -> Option<&str> {
+        let url = self.graph.node_weight(index)?;
+        let response = self.pages.get(url)?.result.as_ref().ok()?;
+        Some(&response.body)
+    }
+
+    /// The pages that `url` has edges to, of any [`EdgeKind`], sorted.
+    pub fn neighbors_of(&self, url: &Url) -> Vec<&Url> {
+        let Some(index) = self.node_by_url(url) else {
+            return Vec::new();
+        };
+        let mut neighbors = Vec::from_iter(self.graph.neighbors(index).map(|it| &self.graph[it]));
+        neighbors.sort();
+        neighbors.dedup();
+        neighbors
+    }
+
+    pub fn contains(&self, url: &Url) -> bool {
+        self.get(url).is_some()
+    }
+
+    /// Seeds that couldn't be fetched. If every seed failed, the crawl found
+    /// nothing.
+    pub fn failed_seeds(&self) -> impl Iterator<Item = (&Url, &FetchError)> {
+        self.seed_indices.iter().filter_map(|index| {
+            let url = &self.graph[*index];
+            self.pages[url].result.as_ref().err().map(|e| (url, e))
+        })
+    }
+
+    pub fn root_failed(&self) -> bool {
+        self.pages[&self.graph[self.root_index]].result.is_err()
+    }
+}
+
+fn in_scope(prefix: &Url, url: &Url) -> bool {
+    let base = prefix.path().trim_end_matches('/');
+    prefix.origin() == url.origin()
+        && url
+            .path()
+            .strip_prefix(base)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Edge {
+    pub kind: EdgeKind,
+    /// How many `<a href>`s on the source page led to the target, if
+    /// [`CrawlConfig::count_links`] is set. Otherwise, and for targets that
+    /// `get_children` found some other way, like in an `<iframe>`, a `srcset`
+    /// or a form, it's 1.
+    pub count: usize,
+    /// One for each `<a href>` on the source page that led to the target, if
+    /// [`CrawlConfig::link_attributes`] is set, and empty otherwise. Targets
+    /// `get_children` found some other way have none.
+    pub attributes: Vec<LinkAttributes>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EdgeKind {
+    /// The target was one of the children of the source page.
+    Link,
+    /// Fetching the source page redirected to the target. Every page in a
+    /// chain of redirects has the same [`NodeValue`].
+    Redirect,
+    /// The source page declared the target as its `<link rel="canonical">`.
+    /// Canonical pages are crawled like children.
+    Canonical,
+}
+
+/// What a page turned out to be, once any redirects were followed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PageResponse {
+    /// Not set for pages that weren't requested over HTTP, like `file://`
+    /// URLs, seeds whose bodies were given, or bodies that were cut short.
+    pub status: Option<StatusCode>,
+    /// Where the page ended up, which is the URL it was requested as unless it
+    /// was redirected.
+    pub final_url: Url,
+    pub content_type: Option<String>,
+    pub body: String,
+}
+
+impl PageResponse {
+    /// A page with no status or content type, like one read from disk.
+    pub fn new(final_url: Url, body: String) -> Self {
+        Self {
+            status: None,
+            final_url,
+            content_type: None,
+            body,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct NodeValue<M = ()> {
+    pub result: Result<PageResponse, FetchError>,
+    /// A response whose status wasn't one of
+    /// [`CrawlConfig::success_statuses`], like a custom 404 page, if its body
+    /// could be read. Its links aren't followed.
+    pub error_response: Option<PageResponse>,
+    /// From the `Last-Modified` header.
+    pub last_modified: Option<SystemTime>,
+    /// Links away from the nearest seed.
+    pub depth: usize,
+    /// The `rel="next"` and `rel="prev"` targets of the page's `Link` headers,
+    /// followed if [`CrawlConfig::follow_link_headers`] is set.
+    pub pagination: Vec<Url>,
+    /// When the fetch started, or when the cached copy was fetched if it came
+    /// from [`CrawlConfig::cache`].
+    pub fetched_at: SystemTime,
+    /// Each way this page was linked to that normalized to a different URL,
+    /// sorted, such as with a fragment or a host in [`CrawlConfig::host_aliases`].
+    pub discovered_as: Vec<Url>,
+    /// Whatever the `classify` hook of [`build_graph_with_metadata`] returned
+    /// for this page.
+    pub metadata: Option<M>,
+}
+
+impl<M> NodeValue<M> {
+    /// The body of [`NodeValue::result`].
+    pub fn body(&self) -> Result<&str, &FetchError> {
+        self.result.as_ref().map(|it| it.body.as_str())
+    }
+
+    /// The final response, whether or not its status was one of
+    /// [`CrawlConfig::success_statuses`].
+    pub fn response(&self) -> Option<&PageResponse> {
+        self.result.as_ref().ok().or(self.error_response.as_ref())
+    }
+
+    /// Of the final response, so a 404 can be told from a 500 or from a page
+    /// that never answered.
+    pub fn status(&self) -> Option<StatusCode> {
+        match (self.response(), &self.result) {
+            (Some(response), _) => response.status,
+            (None, Err(FetchError::Status(status))) => Some(*status),
+            (None, _) => None,
+        }
+    }
+
+    /// Set if fetching the page at `url` was redirected, to where it ended up.
+    pub fn redirected_to(&self, url: &Url) -> Option<&Url> {
+        self.response()
+            .map(|it| &it.final_url)
+            .filter(|it| *it != url)
+    }
 }
 
+/// `get_children` is called with the response for every page that's fetched,
+/// except those whose content type has an entry in [`CrawlConfig::extractors`],
+/// and with the depth of the page. It should usually use [`extract::links`] for
+/// HTML, against the response's [`final_url`](PageResponse::final_url) so
+/// that relative links on pages that were redirected resolve.
+///
+/// Bodies are decoded as text using the charset in their `Content-Type`, so a
+/// client supplied through [`CrawlConfig`] must be able to decompress whatever
+/// the server sends. Clients built from the config handle gzip, brotli and
+/// deflate.
+///
+/// Panics if a client can't be built from `config`, like [`Client::new`].
 pub async fn build_graph(
-    client: &Client,
+    config: &CrawlConfig,
+    root: Url,
+    get_children: impl Fn(&PageResponse, usize) -> Option<HashSet<Url>> + 'static + Clone,
+) -> CrawlResult {
+    build_graph_with_metadata(config, root, get_children, |_, _| None).await
+}
+
+/// Like [`build_graph`], but `classify` is called with each page that's fetched,
+/// and its return value is kept in [`NodeValue::metadata`].
+pub async fn build_graph_with_metadata<M: Clone>(
+    config: &CrawlConfig,
+    root: Url,
+    get_children: impl Fn(&PageResponse, usize) -> Option<HashSet<Url>> + 'static + Clone,
+    classify: impl Fn(&Url, &str) -> Option<M> + 'static + Clone,
+) -> CrawlResult<M> {
+    crawl(
+        config,
+        vec![(root, None)],
+        get_children,
+        classify,
+        None,
+        None,
+    )
+    .await
+}
+
+/// Like [`build_graph`], but `get_children` is called on tokio's blocking
+/// threads, so that parsing pages uses more than one core. The crawl's own
+/// bookkeeping stays on one task, so a URL is still only ever fetched once.
+/// Worth it when `get_children` is slow, like parsing HTML with
+/// [`extract::links`]. [`CrawlConfig::extractors`] are still called on the
+/// crawl's task.
+///
+/// The returned future is `Send`, so it can be spawned onto a multi-threaded
+/// runtime. Panics if called outside of a tokio runtime.
+pub async fn build_graph_parallel(
+    config: &CrawlConfig,
+    root: Url,
+    get_children: impl Fn(&PageResponse, usize) -> Option<HashSet<Url>> + Send + Sync + 'static + Clone,
+) -> CrawlResult {
+    let offload: Offload = Box::new({
+        let get_children = get_children.clone();
+        move |response, depth| {
+            let get_children = get_children.clone();
+            tokio::task::spawn_blocking(move || get_children(&response, depth))
+                .map(|children| children.ok().flatten())
+                .boxed()
+        }
+    });
+    crawl(
+        config,
+        vec![(root, None)],
+        get_children,
+        |_, _| None,
+        None,
+        Some(offload),
+    )
+    .await
+}
+
+/// Like [`build_graph`], but `body` is used for `root` instead of fetching it,
+/// such as HTML rendered by a headless browser. The root's content type is
+/// unknown, so it always goes to `get_children`.
+pub async fn build_graph_from_seed_body(
+    config: &CrawlConfig,
+    root: Url,
+    body: String,
+    get_children: impl Fn(&PageResponse, usize) -> Option<HashSet<Url>> + 'static + Clone,
+) -> CrawlResult {
+    crawl(
+        config,
+        vec![(root, Some(body))],
+        get_children,
+        |_, _| None,
+        None,
+        None,
+    )
+    .await
+}
+
+/// Like [`build_graph`], but crawls from several pages at once. The depth passed
+/// to `get_children` is the fewest links from any seed, so a page that's
+/// reached from a nearer seed after it's been crawled is crawled again.
+///
+/// Panics if `seeds` is empty.
+pub async fn build_graph_from_seeds(
+    config: &CrawlConfig,
+    seeds: impl IntoIterator<Item = Url>,
+    get_children: impl Fn(&PageResponse, usize) -> Option<HashSet<Url>> + 'static + Clone,
+) -> CrawlResult {
+    let seeds = Vec::from_iter(seeds.into_iter().map(|seed| (seed, None)));
+    assert!(!seeds.is_empty(), "no seeds to crawl from");
+    crawl(config, seeds, get_children, |_, _| None, None, None).await
+}
+
+/// Like [`build_graph`], but yields each [`CrawlEvent`] as the crawl goes,
+/// instead of the graph at the end, for processing pages as they arrive.
+/// [`collect_graph`] folds them into the same graph. The crawl only runs while
+/// the stream is polled, and ends with [`CrawlEvent::Finished`]. Events are
+/// sent here rather than to [`CrawlConfig::events`].
+pub fn crawl_stream(
+    config: &CrawlConfig,
+    root: Url,
+    get_children: impl Fn(&PageResponse, usize) -> Option<HashSet<Url>> + 'static + Clone,
+) -> impl Stream<Item = CrawlEvent> {
+    let mut config = config.clone();
+    let events = config.event_stream();
+    let crawl = async move {
+        crawl(
+            &config,
+            vec![(root, None)],
+            get_children,
+            |_, _| None::<()>,
+            None,
+            None,
+        )
+        .await;
+        // so that `events` ends
+        drop(config);
+        None
+    };
+    stream::select(events.map(Some), stream::once(crawl)).filter_map(future::ready)
+}
+
+/// The pages exactly `depth` links from `root`, found without fetching them,
+/// for estimating how big a site is. Pages nearer the root are fetched as
+/// usual, and [`CrawlConfig::max_depth`] is ignored.
+pub async fn frontier_at_depth(
+    config: &CrawlConfig,
     root: Url,
-    get_children: impl Fn(&Url, &str, usize) -> Option<HashSet<Url>> + 'static + Clone,
-) -> (DiGraph<Url, ()>, HashMap<Url, Result<String, String>>) {
-    let nodes = Default::default();
-    let edges = Default::default();
-    edit_graph(client, root, get_children, &nodes, &edges, 0).await;
-    let nodes = nodes.into_inner();
-    let edges = edges.into_inner();
-    let mut graph = DiGraph::new();
-    let mut indices = HashMap::new();
-    for (url, _) in &nodes {
-        indices.insert(url.clone(), graph.add_node(url.clone()));
-    }
-    for (from, to) in edges {
-        graph.add_edge(indices[&from], indices[&to], ());
-    }
-    (graph, nodes)
-}
-
-#[async_recursion(?Send)]
-#[instrument(skip_all, fields(parent))]
-async fn edit_graph(
-    client: &Client,
-    parent: Url,
-    get_children: impl Fn(&Url, &str, usize) -> Option<HashSet<Url>> + 'static + Clone,
-    nodes: &RwLock<HashMap<Url, Result<String, String>>>,
-    edges: &Mutex<HashSet<(Url, Url)>>,
     depth: usize,
-) {
-    if nodes.read().await.contains_key(&parent) {
-        return;
+    get_children: impl Fn(&PageResponse, usize) -> Option<HashSet<Url>> + 'static + Clone,
+) -> HashSet<Url> {
+    if depth == 0 {
+        return HashSet::from([root]);
     }
-    let res = get_webpage(client, &parent)
-        .await
-        .map_err(|e| e.to_string());
+    let config = CrawlConfig {
+        max_depth: None,
+        ..config.clone()
+    };
+    crawl(
+        &config,
+        vec![(root, None)],
+        get_children,
+        |_, _| None::<()>,
+        Some(depth),
+        None,
+    )
+    .await
+    .frontier
+}
+
+/// Fetches `url` as a crawl would, and calls `get_children` with the response once,
+/// for tools that do their own traversal. Only the settings for how pages are
+/// fetched apply, and the children are normalized.
+///
+/// Panics if a client can't be built from `config`, like [`Client::new`].
+pub async fn crawl_one(
+    config: &CrawlConfig,
+    url: Url,
+    get_children: impl Fn(&PageResponse, usize) -> Option<HashSet<Url>>,
+) -> (Result<PageResponse, FetchError>, HashSet<Url>) {
+    let (client, throttle) = match &config.context {
+        Some(context) => (context.client.clone(), context.throttle.clone()),
+        None => (
+            config.client(),
+            config.max_bytes_per_sec.map(Throttle::new).map(Arc::new),
+        ),
+    };
+    let requests = AtomicUsize::new(0);
+    match get_webpage(config, &client, throttle.as_deref(), &requests, &url).await {
+        Ok(fetched) => {
+            let response = PageResponse {
+                status: fetched.status,
+                // the body belongs to the last url we were redirected to
+                final_url: fetched.redirects.last().unwrap_or(&url).clone(),
+                content_type: fetched.content_type,
+                body: fetched.body,
+            };
+            let normalizer = Normalizer::new(config);
+            let children = get_children(&response, 0).unwrap_or_default();
+            let children =
+                HashSet::from_iter(children.into_iter().map(|it| normalizer.normalize(it)));
+            (Ok(response), children)
+        }
+        Err(e) => (Err(e), HashSet::new()),
+    }
+}
+
+/// What [`estimate_size`] found.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct SizeEstimate {
+    /// How many pages are at each depth up to the one sampled. Those at the
+    /// last depth were found, but not fetched.
+    pub levels: Vec<usize>,
+    /// The ratio of the last two `levels`, assumed to hold for the depths that
+    /// weren't sampled.
+    pub branching_factor: f64,
+    /// How many pages a crawl to [`CrawlConfig::max_depth`] would find, or
+    /// `None` if there's no maximum and the site looks like it keeps growing.
+    pub pages: Option<usize>,
+}
+
+/// Crawls `sample_depth` links from `root` (at least one) with
+/// [`frontier_at_depth`], then extrapolates from how much each level grew to
+/// guess how many pages a full crawl would find. Only a guess: sites tend to
+/// link back to pages that have already been seen, so deeper levels grow more
+/// slowly than shallow ones.
+pub async fn estimate_size(
+    config: &CrawlConfig,
+    root: Url,
+    sample_depth: usize,
+    get_children: impl Fn(&PageResponse, usize) -> Option<HashSet<Url>> + 'static + Clone,
+) -> SizeEstimate {
+    let sample_depth = sample_depth.max(1);
+    let sample = crawl(
+        &CrawlConfig {
+            max_depth: None,
+            ..config.clone()
+        },
+        vec![(root, None)],
+        get_children,
+        |_, _| None::<()>,
+        Some(sample_depth),
+        None,
+    )
+    .await;
+    let histogram = &sample.stats.depth_histogram;
+    let mut levels = Vec::from_iter(
+        (0..sample_depth).map(|depth| histogram.get(&depth).copied().unwrap_or_default()),
+    );
+    levels.push(sample.frontier.len());
+    let (previous, last) = (levels[sample_depth - 1] as f64, levels[sample_depth] as f64);
+    let branching_factor = match previous > 0.0 {
+        true => last / previous,
+        false => 0.0,
+    };
+    let sampled = levels.iter().sum::<usize>();
+    let pages = match config.max_depth {
+        Some(max) if max <= sample_depth => Some(levels[..=max].iter().sum()),
+        _ if branching_factor == 0.0 => Some(sampled),
+        Some(max) => {
+            let deeper = (1..=max - sample_depth)
+                .map(|it| last * branching_factor.powi(it as i32))
+                .sum::<f64>();
+            Some(sampled.saturating_add(deeper as usize))
+        }
+        None if branching_factor < 1.0 => {
+            let deeper = last * branching_factor / (1.0 - branching_factor);
+            Some(sampled.saturating_add(deeper as usize))
+        }
+        None => None,
+    };
+    SizeEstimate {
+        levels,
+        branching_factor,
+        pages,
+    }
+}
+
+async fn crawl<M: Clone>(
+    config: &CrawlConfig,
+    seeds: Vec<(Url, Option<String>)>,
+    get_children: impl Fn(&PageResponse, usize) -> Option<HashSet<Url>> + 'static + Clone,
+    classify: impl Fn(&Url, &str) -> Option<M> + 'static + Clone,
+    stop_at: Option<usize>,
+    offload: Option<Offload>,
+) -> CrawlResult<M> {
+    let deadline = config
+        .max_duration
+        .map(|duration| tokio::time::Instant::now() + duration);
+    let normalizer = Normalizer::new(config);
+    let mut originals = HashMap::new();
+    let seeds = Vec::from_iter(
+        seeds
+            .into_iter()
+            .map(|(seed, body)| (normalize_from(&normalizer, &mut originals, seed), body)),
+    );
+    let (journal, records) = match &config.journal {
+        Some(path) => match Journal::open(path) {
+            Ok((journal, records)) => (Some(journal), records),
+            Err(e) => {
+                warn!(
+                    "Couldn't open the journal at {}, crawling without one: {e}",
+                    path.display()
+                );
+                (None, Vec::new())
+            }
+        },
+        None => (None, Vec::new()),
+    };
+    let mut nodes = HashMap::new();
+    let mut edges = HashSet::new();
+    for Record {
+        urls,
+        page,
+        edges: found,
+    } in records
     {
-        let mut write = nodes.write().await;
-        match write.contains_key(&parent) {
-            true => return,
-            false => {
-                info!("Add nodes from {parent}");
-                write.insert(parent.clone(), res.clone());
-                drop(write);
-
-                if let Ok(s) = res {
-                    if let Some(children) = get_children(&parent, &s, depth) {
-                        info!("Disovered {} children", children.len());
-                        let mut write = edges.lock().await;
-                        for child in &children {
-                            let newly_added = write.insert((parent.clone(), child.clone()));
-                            assert!(newly_added, "logic error - created same edge twice");
+        let value = page.into_value(&urls[0], &classify);
+        for url in urls {
+            nodes.insert(url, value.clone());
+        }
+        edges.extend(found);
+    }
+    // carry on from the pages that were found but not visited
+    let mut start = Vec::from_iter(edges.iter().filter_map(|(from, to, _)| {
+        match (nodes.get(from), nodes.contains_key(to)) {
+            (Some(from), false) => Some((to.clone(), from.depth + 1)),
+            _ => None,
+        }
+    }));
+    start.sort();
+    let crawl = Crawl {
+        normalizer: normalizer.clone(),
+        config,
+        client: match &config.context {
+            Some(context) => context.client.clone(),
+            None => config.client(),
+        },
+        get_children,
+        classify,
+        frontier: Mutex::new(HashSet::from_iter(start.iter().map(|(url, _)| url.clone()))),
+        // before `nodes` is moved
+        visited: AtomicUsize::new(nodes.len()),
+        nodes: RwLock::new(nodes),
+        edges: Mutex::new(edges),
+        in_flight: Default::default(),
+        throttle: match &config.context {
+            Some(context) => context.throttle.clone(),
+            None => config.max_bytes_per_sec.map(Throttle::new).map(Arc::new),
+        },
+        requests: AtomicUsize::new(0),
+        fetches: Semaphore::new(config.max_concurrency.max(1)),
+        hosts: Default::default(),
+        next_fetch: config
+            .context
+            .as_ref()
+            .map(|context| context.next_fetch.clone())
+            .unwrap_or_default(),
+        robots: config
+            .context
+            .as_ref()
+            .map(|context| context.robots.clone())
+            .unwrap_or_default(),
+        journal,
+        mirror: config.save_bodies_to.clone().map(Mirror::new),
+        stop_at,
+        offload,
+        originals: std::sync::Mutex::new(originals),
+        counts: Default::default(),
+        attributes: Default::default(),
+        origins: HashSet::from_iter(seeds.iter().map(|(seed, _)| seed.origin())),
+        cancellation: config.cancellation.child_token(),
+        added: Notify::new(),
+    };
+    let logged_in = match &config.login {
+        Some(login) => login(crawl.client.clone()).await,
+        None => Ok(()),
+    };
+    if logged_in.is_ok() {
+        for (seed, body) in &seeds {
+            if crawl.nodes.read().await.contains_key(seed) {
+                continue;
+            }
+            match body {
+                Some(body) => {
+                    let mut children = Vec::from_iter(crawl.seed(seed, body.clone()).await);
+                    children.sort();
+                    start.extend(children.into_iter().map(|child| (child, 1)));
+                }
+                None => start.push((seed.clone(), 0)),
+            }
+        }
+        tokio::select! {
+            _ = crawl.traverse(start) => {}
+            _ = crawl.checkpoints() => unreachable!("checkpoints are taken until the crawl ends"),
+            _ = cancel_at(&crawl.cancellation, deadline) => unreachable!("never returns"),
+        }
+    }
+    if let Some((_, path)) = &config.checkpoint {
+        crawl.checkpoint(path).await;
+    }
+    let seeds = Vec::from_iter(seeds.into_iter().map(|(seed, _)| seed));
+    let requests = crawl.requests;
+    let mut nodes = crawl.nodes.into_inner();
+    let mut edges = crawl.edges.into_inner();
+    let mut frontier = crawl.frontier.into_inner();
+    frontier.retain(|url| !nodes.contains_key(url));
+    for (url, forms) in crawl.originals.into_inner().unwrap() {
+        if let Some(node) = nodes.get_mut(&url) {
+            node.discovered_as = Vec::from_iter(forms);
+        }
+    }
+    // only if the crawl was cancelled before it started, or never started
+    for seed in &seeds {
+        nodes.entry(seed.clone()).or_insert_with(|| NodeValue {
+            result: Err(match &logged_in {
+                Ok(()) => FetchError::Cancelled,
+                Err(e) => FetchError::Login(e.clone()),
+            }),
+            error_response: None,
+            last_modified: None,
+            depth: 0,
+            pagination: Vec::new(),
+            fetched_at: SystemTime::now(),
+            discovered_as: Vec::new(),
+            metadata: None,
+        });
+    }
+    let mut counts = crawl.counts.into_inner().unwrap();
+    let mut attributes = crawl.attributes.into_inner().unwrap();
+    let aliases = match config.merge_canonicals {
+        true => merge_canonicals(&mut nodes, &mut edges, &mut counts, &mut attributes),
+        false => HashMap::new(),
+    };
+    // the index borrows its keys from `nodes`, so each URL is only cloned into
+    // the graph
+    let mut graph = DiGraph::with_capacity(nodes.len(), edges.len());
+    let mut indices = HashMap::with_capacity(nodes.len());
+    for url in nodes.keys() {
+        indices.insert(url, graph.add_node(url.clone()));
+    }
+    for (from_url, to_url, kind) in edges {
+        // children are only missing if the crawl was cancelled
+        if let (Some(from), Some(to)) = (indices.get(&from_url), indices.get(&to_url)) {
+            let key = (from_url, to_url);
+            let (count, attributes) = match kind {
+                EdgeKind::Link => (
+                    counts.get(&key).copied().unwrap_or(1),
+                    attributes.remove(&key).unwrap_or_default(),
+                ),
+                _ => (1, Vec::new()),
+            };
+            let edge = Edge {
+                kind,
+                count,
+                attributes,
+            };
+            graph.add_edge(*from, *to, edge);
+        }
+    }
+    let seed_indices = Vec::from_iter(
+        seeds
+            .iter()
+            .map(|seed| indices[aliases.get(seed).unwrap_or(seed)]),
+    );
+    let mut stats = CrawlStats {
+        pages: nodes.len(),
+        edges: graph.edge_count(),
+        frontier: frontier.len(),
+        requests: requests.into_inner(),
+        ..Default::default()
+    };
+    for node in nodes.values() {
+        *stats.depth_histogram.entry(node.depth).or_default() += 1;
+        match node.result {
+            Ok(_) => {}
+            Err(FetchError::Skipped(_)) => stats.skipped += 1,
+            Err(ref e) => {
+                stats.failed += 1;
+                *stats.errors.entry(e.category()).or_default() += 1;
+            }
+        }
+    }
+    config.emit(CrawlEvent::Finished(stats.clone()));
+    CrawlResult {
+        graph,
+        root_index: seed_indices[0],
+        seed_indices,
+        pages: nodes,
+        frontier,
+        stats,
+        normalizer,
+    }
+}
+
+/// See [`CrawlConfig::merge_canonicals`]. Returns the pages that were folded,
+/// and what they were folded into.
+fn merge_canonicals<M>(
+    nodes: &mut HashMap<Url, NodeValue<M>>,
+    edges: &mut HashSet<(Url, Url, EdgeKind)>,
+    counts: &mut HashMap<(Url, Url), usize>,
+    attributes: &mut HashMap<(Url, Url), Vec<LinkAttributes>>,
+) -> HashMap<Url, Url> {
+    let mut aliases = HashMap::new();
+    for (from, to, kind) in edges.iter() {
+        if *kind == EdgeKind::Canonical && nodes.get(to).is_some_and(|it| it.result.is_ok()) {
+            aliases.insert(from.clone(), to.clone());
+        }
+    }
+    let chained = HashSet::<Url>::from_iter(aliases.keys().cloned());
+    aliases.retain(|_, to| !chained.contains(to));
+    for (alias, canonical) in &aliases {
+        let folded = nodes
+            .remove(alias)
+            .expect("canonical edges are from fetched pages");
+        let node = nodes.get_mut(canonical).expect("checked above");
+        node.depth = node.depth.min(folded.depth);
+        node.discovered_as.push(alias.clone());
+        node.discovered_as.extend(folded.discovered_as);
+        node.discovered_as.sort();
+        node.discovered_as.dedup();
+    }
+    let map = |url: Url| aliases.get(&url).cloned().unwrap_or(url);
+    // linking to the canonical page is the same as linking to itself now
+    let merged = |from: &Url, to: &Url| from != to && map(from.clone()) == map(to.clone());
+    *edges = HashSet::from_iter(
+        edges
+            .drain()
+            .filter(|(from, to, _)| !merged(from, to))
+            .map(|(from, to, kind)| (map(from), map(to), kind)),
+    );
+    // where two edges were merged, the first one's details are kept
+    let mut sorted = Vec::from_iter(counts.drain());
+    sorted.sort();
+    for ((from, to), count) in sorted {
+        if !merged(&from, &to) {
+            counts.entry((map(from), map(to))).or_insert(count);
+        }
+    }
+    let mut sorted = Vec::from_iter(attributes.drain());
+    sorted.sort_by(|(a, _), (b, _)| a.cmp(b));
+    for ((from, to), found) in sorted {
+        if !merged(&from, &to) {
+            attributes.entry((map(from), map(to))).or_insert(found);
+        }
+    }
+    aliases
+}
+
+/// Finds a page's children away from the crawl's task.
+type Offload =
+    Box<dyn Fn(PageResponse, usize) -> BoxFuture<'static, Option<HashSet<Url>>> + Send + Sync>;
+
+struct Crawl<'a, F, C, M> {
+    config: &'a CrawlConfig,
+    client: Client,
+    get_children: F,
+    classify: C,
+    nodes: RwLock<HashMap<Url, NodeValue<M>>>,
+    edges: Mutex<HashSet<(Url, Url, EdgeKind)>>,
+    frontier: Mutex<HashSet<Url>>,
+    /// Pages being fetched, and the nearest depth they've been reached at.
+    in_flight: Mutex<HashMap<Url, usize>>,
+    throttle: Option<Arc<Throttle>>,
+    /// See [`CrawlStats::requests`].
+    requests: AtomicUsize,
+    /// Pages visited, for [`CrawlConfig::max_pages`].
+    visited: AtomicUsize,
+    /// See [`CrawlConfig::max_concurrency`].
+    fetches: Semaphore,
+    /// See [`CrawlConfig::per_host_concurrency`].
+    hosts: std::sync::Mutex<HashMap<String, Arc<Semaphore>>>,
+    /// From [`CrawlConfig::context`], if there is one.
+    next_fetch: NextFetch,
+    /// From [`CrawlConfig::context`], if there is one.
+    robots: RobotsCache,
+    normalizer: Normalizer,
+    journal: Option<Journal>,
+    mirror: Option<Mirror>,
+    /// Pages at this depth are left in the frontier instead of being fetched.
+    stop_at: Option<usize>,
+    /// Called instead of `get_children`, see [`build_graph_parallel`].
+    offload: Option<Offload>,
+    /// See [`NodeValue::discovered_as`].
+    originals: std::sync::Mutex<HashMap<Url, BTreeSet<Url>>>,
+    /// See [`CrawlConfig::count_links`]. Links that aren't here were seen once.
+    counts: std::sync::Mutex<HashMap<(Url, Url), usize>>,
+    /// See [`CrawlConfig::link_attributes`].
+    attributes: std::sync::Mutex<HashMap<(Url, Url), Vec<LinkAttributes>>>,
+    /// Of the seeds, for [`Scope::SameOrigin`].
+    origins: HashSet<Origin>,
+    /// A child of [`CrawlConfig::cancellation`], that's also cancelled after
+    /// [`CrawlConfig::idle_timeout`].
+    cancellation: CancellationToken,
+    /// Notified whenever a page is added, for [`CheckpointInterval::Pages`].
+    added: Notify,
+}
+
+/// Normalizes `url`, remembering how it was written if that changed it.
+fn normalize_from(
+    normalizer: &Normalizer,
+    originals: &mut HashMap<Url, BTreeSet<Url>>,
+    url: Url,
+) -> Url {
+    let normalized = normalizer.normalize(url.clone());
+    if normalized != url {
+        originals.entry(normalized.clone()).or_default().insert(url);
+    }
+    normalized
+}
+
+impl<F, C, M> Crawl<'_, F, C, M>
+where
+    F: Fn(&PageResponse, usize) -> Option<HashSet<Url>>,
+    C: Fn(&Url, &str) -> Option<M>,
+    M: Clone,
+{
+    /// Fetches `parent` unless it's already been visited, returning the children
+    /// to crawl next, and the depth they were found at, which is nearer than
+    /// `depth` if the page was reached again while it was being fetched.
+    #[instrument(skip(self))]
+    async fn visit(&self, parent: Url, depth: usize) -> (HashSet<Url>, usize) {
+        let cancellation = &self.cancellation;
+        if cancellation.is_cancelled() || self.stop_at.is_some_and(|stop| depth >= stop) {
+            return (HashSet::new(), depth);
+        }
+        self.frontier.lock().await.remove(&parent);
+        let mut in_flight = self.in_flight.lock().await;
+        if self.nodes.read().await.contains_key(&parent) {
+            drop(in_flight);
+            return (self.revisit(parent, depth).await, depth);
+        }
+        if let Some(nearest) = in_flight.get_mut(&parent) {
+            // whoever's fetching it crawls its children from the nearest depth
+            *nearest = depth.min(*nearest);
+            return (HashSet::new(), depth);
+        }
+        let budget = self
+            .visited
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |visited| {
+                match self.config.max_pages {
+                    Some(max) if visited >= max => None,
+                    _ => Some(visited + 1),
+                }
+            });
+        if budget.is_err() {
+            drop(in_flight);
+            info!("Reached the maximum number of pages, leaving {parent} in the frontier");
+            self.frontier.lock().await.insert(parent);
+            return (HashSet::new(), depth);
+        }
+        in_flight.insert(parent.clone(), depth);
+        drop(in_flight);
+        let cached = self.fresh_cached(&parent);
+        let fetched_at = cached.map_or_else(SystemTime::now, |page| page.fetched_at);
+        // seeds are always fetched
+        let known = depth > 0 && self.config.known.contains(&parent);
+        let mut skip_reason = known
+            .then_some(SkipReason::Known)
+            .or_else(|| self.skip_reason(&parent));
+        if skip_reason.is_none() && cached.is_none() && !self.robots_allow(&parent).await {
+            skip_reason = Some(SkipReason::Robots);
+        }
+        let res = match (skip_reason, cached) {
+            (Some(reason), _) => Err(FetchError::Skipped(reason)),
+            (None, Some(page)) => Ok(Fetched {
+                body: page
+                    .result
+                    .as_ref()
+                    .map(|it| it.body.clone())
+                    .unwrap_or_default(),
+                status: page.status(),
+                content_type: page.response().and_then(|it| it.content_type.clone()),
+                last_modified: page.last_modified,
+                pagination: page.pagination.clone(),
+                redirects: Vec::from_iter(page.redirected_to(&parent).cloned()),
+            }),
+            (None, None) => tokio::select! {
+                res = async {
+                    self.config.pause.wait().await;
+                    self.host_delay(&parent).await;
+                    let _host = self.host_permit(&parent).await;
+                    // never closed
+                    let _permit = self.fetches.acquire().await.ok();
+                    if let Some(on_fetch) = &self.config.on_fetch {
+                        on_fetch(&parent);
+                    }
+                    self.config.emit(CrawlEvent::FetchStarted {
+                        url: parent.clone(),
+                    });
+                    get_any_webpage(
+                        self.config,
+                        &self.client,
+                        self.throttle.as_deref(),
+                        &self.requests,
+                        &parent,
+                    )
+                    .await
+                } => res,
+                _ = cancellation.cancelled() => Err(FetchError::Cancelled),
+            },
+        };
+        // the body belongs to the last url we were redirected to
+        let (res, error_response, last_modified, pagination, redirects) = match res {
+            Ok(Fetched {
+                body,
+                status,
+                content_type,
+                last_modified,
+                pagination,
+                redirects,
+            }) => {
+                let response = PageResponse {
+                    status,
+                    final_url: redirects.last().unwrap_or(&parent).clone(),
+                    content_type,
+                    body,
+                };
+                let (res, error_response) = match status {
+                    Some(status) if !self.config.success_statuses.contains(&status.as_u16()) => {
+                        (Err(FetchError::Status(status)), Some(response))
+                    }
+                    _ => (Ok(response), None),
+                };
+                (res, error_response, last_modified, pagination, redirects)
+            }
+            Err(e) => (Err(e), None, None, Vec::new(), Vec::new()),
+        };
+        let url = redirects.last().unwrap_or(&parent).clone();
+        let mut value = NodeValue {
+            metadata: res
+                .as_ref()
+                .ok()
+                .and_then(|it| (self.classify)(&it.final_url, &it.body)),
+            result: res,
+            error_response,
+            last_modified,
+            depth,
+            pagination,
+            fetched_at,
+            discovered_as: Vec::new(),
+        };
+        if let (Some(mirror), Ok(response)) = (&self.mirror, &value.result) {
+            mirror.save(&url, &response.body);
+        }
+        let mut in_flight = self.in_flight.lock().await;
+        let depth = in_flight.remove(&parent).unwrap_or(depth);
+        value.depth = depth;
+        let mut write = self.nodes.write().await;
+        drop(in_flight);
+        if write.contains_key(&parent) {
+            drop(write);
+            return (self.revisit(parent, depth).await, depth);
+        }
+        info!("Add nodes from {parent}");
+        write.insert(parent.clone(), value.clone());
+        self.node_fetched(&parent, &value);
+        // each hop gets a node, unless another page already redirected there
+        let mut hops = Vec::new();
+        let mut urls = vec![parent.clone()];
+        let mut from = parent.clone();
+        let mut claimed = true;
+        for to in redirects {
+            hops.push((from, to.clone(), EdgeKind::Redirect));
+            if write.contains_key(&to) {
+                claimed = false;
+                break;
+            }
+            write.insert(to.clone(), value.clone());
+            self.node_fetched(&to, &value);
+            urls.push(to.clone());
+            from = to;
+        }
+        drop(write);
+        let mut write = self.edges.lock().await;
+        let mut added = Vec::new();
+        for (from, to, kind) in hops {
+            if write.insert((from.clone(), to.clone(), kind)) {
+                self.edge_discovered(from.clone(), to.clone(), kind);
+                added.push((from, to, kind));
+            }
+        }
+        drop(write);
+        if !claimed {
+            self.record(urls, &value, added);
+            return (HashSet::new(), depth);
+        }
+        let (children, edges) = self.expand(&url, &value, depth).await;
+        added.extend(edges);
+        self.record(urls, &value, added);
+        (children, depth)
+    }
+
+    async fn host_permit(&self, url: &Url) -> Option<tokio::sync::OwnedSemaphorePermit> {
+        let max = self.config.per_host_concurrency?;
+        let semaphore = self
+            .hosts
+            .lock()
+            .unwrap()
+            .entry(url.host_str().unwrap_or_default().to_owned())
+            .or_insert_with(|| Arc::new(Semaphore::new(max.max(1))))
+            .clone();
+        // never closed
+        semaphore.acquire_owned().await.ok()
+    }
+
+    /// Waits for [`CrawlConfig::per_host_delay`], or the host's `Crawl-delay`
+    /// if that's longer and [`CrawlConfig::respect_robots`] is on.
+    async fn host_delay(&self, url: &Url) {
+        let crawl_delay = match self.config.respect_robots {
+            true => self
+                .robots
+                .lock()
+                .unwrap()
+                .get(&url.origin())
+                .and_then(|robots| robots.get()?.crawl_delay),
+            false => None,
+        };
+        let Some(delay) = self.config.per_host_delay.max(crawl_delay) else {
+            return;
+        };
+        let now = Instant::now();
+        let at = {
+            let mut next_fetch = self.next_fetch.lock().unwrap();
+            let next = next_fetch
+                .entry(url.host_str().unwrap_or_default().to_owned())
+                .or_insert(now);
+            let at = now.max(*next);
+            *next = at + delay;
+            at
+        };
+        tokio::time::sleep_until(at).await;
+    }
+
+    /// Whether `url`'s host lets us fetch it, fetching its `robots.txt` if this
+    /// is the first page there.
+    async fn robots_allow(&self, url: &Url) -> bool {
+        if !self.config.respect_robots || !matches!(url.scheme(), "http" | "https") {
+            return true;
+        }
+        let robots = self
+            .robots
+            .lock()
+            .unwrap()
+            .entry(url.origin())
+            .or_default()
+            .clone();
+        robots
+            .get_or_init(|| async {
+                let mut robots_txt = url.clone();
+                robots_txt.set_path("/robots.txt");
+                robots_txt.set_query(None);
+                robots_txt.set_fragment(None);
+                let fetched = tokio::select! {
+                    fetched = async {
+                        self.host_delay(url).await;
+                        let _host = self.host_permit(url).await;
+                        // never closed
+                        let _permit = self.fetches.acquire().await.ok();
+                        get_webpage(
+                            self.config,
+                            &self.client,
+                            self.throttle.as_deref(),
+                            &self.requests,
+                            &robots_txt,
+                        )
+                        .await
+                    } => fetched,
+                    // the page won't be fetched either
+                    _ = self.cancellation.cancelled() => Err(FetchError::Cancelled),
+                };
+                match fetched {
+                    Ok(fetched) => Robots::parse(&fetched.body, self.config.user_agent.as_deref()),
+                    Err(e) => {
+                        debug!("No robots.txt for {url}: {e}");
+                        Robots::default()
+                    }
+                }
+            })
+            .await
+            .allows(url)
+    }
+
+    fn normalize(&self, url: Url) -> Url {
+        normalize_from(&self.normalizer, &mut self.originals.lock().unwrap(), url)
+    }
+
+    fn node_fetched(&self, url: &Url, value: &NodeValue<M>) {
+        self.added.notify_one();
+        // so that bodies aren't copied for nobody
+        if self.config.events.is_some() {
+            self.config.emit(CrawlEvent::NodeFetched {
+                url: url.clone(),
+                depth: value.depth,
+                result: value.result.clone(),
+            });
+        }
+        match &value.result {
+            Ok(_) => {}
+            Err(FetchError::Skipped(reason)) => self.config.emit(CrawlEvent::Skipped {
+                url: url.clone(),
+                reason: reason.clone(),
+            }),
+            Err(error) => self.config.emit(CrawlEvent::Error {
+                url: url.clone(),
+                error: error.clone(),
+            }),
+        }
+    }
+
+    fn edge_discovered(&self, from: Url, to: Url, kind: EdgeKind) {
+        self.config
+            .emit(CrawlEvent::EdgeDiscovered { from, to, kind })
+    }
+
+    /// A successfully fetched page from [`CrawlConfig::cache`] that's younger
+    /// than [`CrawlConfig::cache_ttl`].
+    fn fresh_cached(&self, url: &Url) -> Option<&NodeValue> {
+        self.config.cache.get(url).filter(|page| {
+            page.result.is_ok()
+                && page
+                    .fetched_at
+                    .elapsed()
+                    .is_ok_and(|age| age < self.config.cache_ttl)
+        })
+    }
+
+    fn skip_reason(&self, url: &Url) -> Option<SkipReason> {
+        let config = self.config;
+        if let Some(limit) = config.max_url_length {
+            if url.as_str().len() > limit {
+                return Some(SkipReason::UrlTooLong { limit });
+            }
+        }
+        if let Some(prefix) = &config.scope_prefix {
+            if !in_scope(prefix, url) {
+                return Some(SkipReason::OutOfScope);
+            }
+        }
+        if config.scope == Scope::SameOrigin && !self.origins.contains(&url.origin()) {
+            return Some(SkipReason::OutOfScope);
+        }
+        let segments = Vec::from_iter(
+            url.path_segments()
+                .into_iter()
+                .flatten()
+                .filter(|it| !it.is_empty()),
+        );
+        if let Some(limit) = config.max_path_segments {
+            if segments.len() > limit {
+                return Some(SkipReason::TooManyPathSegments { limit });
+            }
+        }
+        if let Some(limit) = config.max_segment_repeats {
+            let mut counts = HashMap::new();
+            for segment in &segments {
+                let count = counts.entry(*segment).or_insert(0);
+                *count += 1;
+                if *count > limit {
+                    return Some(SkipReason::RepeatedPathSegment {
+                        segment: segment.to_string(),
+                        limit,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Records a seed whose body was given rather than fetched, returning its
+    /// children.
+    async fn seed(&self, url: &Url, body: String) -> HashSet<Url> {
+        let value = NodeValue {
+            metadata: (self.classify)(url, &body),
+            result: Ok(PageResponse::new(url.clone(), body)),
+            error_response: None,
+            last_modified: None,
+            depth: 0,
+            pagination: Vec::new(),
+            fetched_at: SystemTime::now(),
+            discovered_as: Vec::new(),
+        };
+        self.nodes.write().await.insert(url.clone(), value.clone());
+        self.node_fetched(url, &value);
+        let (children, edges) = self.expand(url, &value, 0).await;
+        self.record(vec![url.clone()], &value, edges);
+        children
+    }
+
+    /// Crawls an already fetched page's children again if it's now been reached
+    /// from nearer a seed.
+    async fn revisit(&self, url: Url, depth: usize) -> HashSet<Url> {
+        let mut write = self.nodes.write().await;
+        let Some(node) = write.get_mut(&url).filter(|node| node.depth > depth) else {
+            return HashSet::new();
+        };
+        node.depth = depth;
+        let mut urls = vec![url.clone()];
+        // the children were found from the page that was redirected to
+        let url = node.redirected_to(&url).cloned().unwrap_or(url);
+        let Some(node) = write.get_mut(&url).filter(|node| node.depth >= depth) else {
+            return HashSet::new();
+        };
+        node.depth = depth;
+        let value = node.clone();
+        drop(write);
+        if urls[0] != url {
+            urls.push(url.clone());
+        }
+        let (children, edges) = self.expand(&url, &value, depth).await;
+        self.record(urls, &value, edges);
+        children
+    }
+
+    fn record(&self, urls: Vec<Url>, value: &NodeValue<M>, edges: Vec<(Url, Url, EdgeKind)>) {
+        if let Some(journal) = &self.journal {
+            journal.write(&Record {
+                urls,
+                page: Page::new(value),
+                edges,
+            });
+        }
+    }
+
+    /// Finds the children of a fetched page, and records its edges, returning
+    /// those that are new.
+    async fn expand(
+        &self,
+        url: &Url,
+        value: &NodeValue<M>,
+        depth: usize,
+    ) -> (HashSet<Url>, Vec<(Url, Url, EdgeKind)>) {
+        if self.config.max_depth.is_some_and(|max| depth >= max) {
+            self.config.emit(CrawlEvent::DepthLimited {
+                url: url.clone(),
+                depth,
+            });
+            return Default::default();
+        }
+        let cancellation = &self.cancellation;
+        let keep = |s: &str| match &self.config.should_keep {
+            Some(should_keep) => should_keep(url, s),
+            None => true,
+        };
+        let response = match &value.result {
+            Ok(response) => Some(Cow::Borrowed(response)),
+            // a truncated page might still have useful links
+            Err(FetchError::PartialBody { bytes, .. } | FetchError::BodyTimeout { bytes }) => {
+                let body = String::from_utf8_lossy(bytes).into_owned();
+                Some(Cow::Owned(PageResponse::new(url.clone(), body)))
+            }
+            Err(_) => None,
+        };
+        let response = match &self.config.preprocess_body {
+            Some(preprocess) => response.map(|response| {
+                let mut response = response.into_owned();
+                response.body = preprocess(url, response.body);
+                Cow::Owned(response)
+            }),
+            None => response,
+        };
+        let mime = response
+            .as_ref()
+            .and_then(|response| response.content_type.as_deref())
+            .and_then(|content_type| content_type.parse::<Mime>().ok());
+        let extractor = mime
+            .as_ref()
+            .and_then(|mime| self.config.extractors.get(mime.essence_str()));
+        let response = response.filter(|response| keep(&response.body));
+        let children = match (&response, extractor, &self.offload) {
+            (None, _, _) => None,
+            (Some(response), Some(extract), _) => extract(response, depth),
+            (Some(response), None, Some(offload)) => {
+                tokio::select! {
+                    children = offload(response.clone().into_owned(), depth) => children,
+                    _ = cancellation.cancelled() => None,
+                }
+            }
+            (Some(response), None, None) => (self.get_children)(response, depth),
+        };
+        let Some((mut children, canonical, counts, mut attributes)) = response
+            .as_ref()
+            .zip(children)
+            .map(|(response, mut children)| {
+                let s = &response.body;
+                if self.config.extract_from_scripts && extractor.is_none() {
+                    let is_script = mime.as_ref().map_or(url.path().ends_with(".js"), |mime| {
+                        matches!(mime.subtype().as_str(), "javascript" | "x-javascript")
+                    });
+                    children.extend(match is_script {
+                        true => extract::script_paths(url, s),
+                        false => extract::script_links(url, s),
+                    });
+                }
+                let mut counts = HashMap::<Url, usize>::new();
+                let mut attributes = HashMap::<Url, Vec<_>>::new();
+                // one parse for both, since each anchor is a link
+                if self.config.count_links || self.config.link_attributes {
+                    for (link, found) in extract::anchors(url, s) {
+                        let link = self.normalizer.normalize(link);
+                        if self.config.count_links {
+                            *counts.entry(link.clone()).or_default() += 1;
+                        }
+                        if self.config.link_attributes {
+                            attributes.entry(link).or_default().push(found);
                         }
-                        drop(write);
-                        join_all(children.into_iter().map(|new_parent| {
-                            edit_graph(
-                                client,
-                                new_parent,
-                                get_children.clone(),
-                                nodes,
-                                edges,
-                                depth + 1,
-                            )
-                        }))
-                        .await;
+                    }
+                }
+                (
+                    HashSet::from_iter(children.into_iter().map(|it| self.normalize(it))),
+                    extract::canonical(url, s)
+                        .map(|it| self.normalize(it))
+                        .filter(|it| it != url),
+                    counts,
+                    attributes,
+                )
+            })
+        else {
+            return Default::default();
+        };
+        if let (true, None, Some(render), Some(response)) = (
+            children.is_empty(),
+            extractor,
+            &self.config.render_fallback,
+            &response,
+        ) {
+            let rendered = tokio::select! {
+                rendered = render(url) => rendered,
+                _ = cancellation.cancelled() => None,
+            };
+            let rendered = rendered.map(|body| PageResponse {
+                status: response.status,
+                final_url: response.final_url.clone(),
+                content_type: response.content_type.clone(),
+                body,
+            });
+            if let Some(children_after_render) =
+                rendered.and_then(|rendered| (self.get_children)(&rendered, depth))
+            {
+                children = HashSet::from_iter(
+                    children_after_render
+                        .into_iter()
+                        .map(|it| self.normalize(it)),
+                );
+            }
+        }
+        if self.config.follow_link_headers {
+            children.extend(value.pagination.iter().map(|it| self.normalize(it.clone())));
+        }
+        info!("Disovered {} children", children.len());
+        let mut write = self.edges.lock().await;
+        let full = |edges: &HashSet<_>| self.config.max_edges.is_some_and(|max| edges.len() >= max);
+        let mut added = Vec::new();
+        // sorted so that the same children are kept when there are too many edges
+        let mut sorted = Vec::from_iter(children.drain());
+        sorted.sort();
+        debug_assert!(
+            sorted.windows(2).all(|pair| pair[0] != pair[1]),
+            "children are deduplicated after normalization"
+        );
+        for child in sorted {
+            let edge = (url.clone(), child.clone(), EdgeKind::Link);
+            if full(&write) && !write.contains(&edge) {
+                continue;
+            }
+            // even on the first visit, a revisit from nearer a seed may have
+            // got here first
+            if write.insert(edge.clone()) {
+                if let Some(&count) = counts.get(&child).filter(|it| **it > 1) {
+                    let key = (url.clone(), child.clone());
+                    self.counts.lock().unwrap().insert(key, count);
+                }
+                if let Some(found) = attributes.remove(&child) {
+                    let key = (url.clone(), child.clone());
+                    self.attributes.lock().unwrap().insert(key, found);
+                }
+                self.edge_discovered(url.clone(), child.clone(), EdgeKind::Link);
+                added.push(edge);
+            }
+            children.insert(child);
+        }
+        if let Some(canonical) = canonical {
+            let edge = (url.clone(), canonical.clone(), EdgeKind::Canonical);
+            if write.contains(&edge) || !full(&write) {
+                if write.insert(edge.clone()) {
+                    self.edge_discovered(url.clone(), canonical.clone(), EdgeKind::Canonical);
+                    added.push(edge);
+                }
+                children.insert(canonical);
+            }
+        }
+        drop(write);
+        self.frontier.lock().await.extend(children.iter().cloned());
+        match cancellation.is_cancelled() {
+            true => (HashSet::new(), added),
+            false => (children, added),
+        }
+    }
+
+    /// Writes [`CrawlConfig::checkpoint`] at every interval, never returning.
+    async fn checkpoints(&self) {
+        let Some((interval, path)) = &self.config.checkpoint else {
+            return std::future::pending().await;
+        };
+        let mut next = self.nodes.read().await.len();
+        loop {
+            match *interval {
+                CheckpointInterval::Elapsed(elapsed) => tokio::time::sleep(elapsed).await,
+                CheckpointInterval::Pages(pages) => {
+                    next += pages.max(1);
+                    while self.nodes.read().await.len() < next {
+                        self.added.notified().await;
                     }
                 }
             }
+            self.checkpoint(path).await;
+        }
+    }
+
+    /// Failures are logged rather than stopping the crawl.
+    async fn checkpoint(&self, path: &Path) {
+        // both locks at once, so that every edge's source is in the snapshot
+        let nodes = self.nodes.read().await;
+        let edges = self.edges.lock().await;
+        let mut found = HashMap::<_, Vec<_>>::new();
+        for edge in edges.iter() {
+            found.entry(&edge.0).or_default().push(edge.clone());
         }
+        let records = Vec::from_iter(nodes.iter().map(|(url, value)| Record {
+            urls: vec![url.clone()],
+            page: Page::new(value),
+            edges: found.remove(url).unwrap_or_default(),
+        }));
+        drop(edges);
+        drop(nodes);
+        if let Err(e) = write_snapshot(path, &records) {
+            warn!("Couldn't write a checkpoint to {}: {e}", path.display());
+        }
+    }
+
+    /// Crawls from an explicit queue of pending pages rather than recursing, so
+    /// long chains of links can't overflow the stack. Has at most
+    /// [`CrawlConfig::max_concurrency`] pages in flight, except for
+    /// [`Strategy::Concurrent`], which visits everything as soon as it's found
+    /// and leaves the fetches to queue for permits.
+    async fn traverse(&self, start: Vec<(Url, usize)>) {
+        traverse(
+            self.config,
+            &self.cancellation,
+            start,
+            |url, depth| async move {
+                let (children, depth) = self.visit(url, depth).await;
+                // `get_children` returns a set, so sort to make the order predictable
+                let mut children = Vec::from_iter(children);
+                children.sort();
+                (children, depth)
+            },
+        )
+        .await
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::{HashMap, HashSet};
+    use std::cell::RefCell;
+    use std::collections::{BTreeMap, HashMap, HashSet};
+    use std::io::Write;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
 
-    use httptest::{matchers::request, responders::status_code, Expectation, Server};
-    use petgraph::graph::DiGraph;
-    use soup::{NodeExt, QueryBuilderExt, Soup};
+    use flate2::{write::GzEncoder, Compression};
+    use futures::StreamExt;
+    use httptest::{
+        all_of,
+        matchers::{contains, request},
+        responders::{cycle, delay_and_then, status_code},
+        Expectation, Server,
+    };
+    use petgraph::{graph::DiGraph, visit::EdgeRef};
+    use reqwest::{header::HeaderValue, StatusCode};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
     use url::Url;
 
-    use crate::build_graph;
+    use crate::{
+        analysis, build_graph, build_graph_from_seed_body, build_graph_from_seeds,
+        build_graph_generic, build_graph_with_metadata, export, extract, CheckpointInterval,
+        ContentExtractor, CrawlConfig, CrawlContext, CrawlEvent, Edge, EdgeKind, FetchError,
+        NodeValue, PageResponse, Profile, Scope, SkipReason, Strategy,
+    };
 
-    const LINK_TO_BAR: &'static str = r#"<a href="/bar">bar</a>"#;
-    const LINK_TO_FOO: &'static str = r#"<a href="/foo">foo</a>"#;
+    const LINK_TO_BAR: &str = r#"<a href="/bar">bar</a>"#;
+    const LINK_TO_FOO: &str = r#"<a href="/foo">foo</a>"#;
 
     #[tokio::test]
     async fn cyclic() {
@@ -159,58 +1608,3165 @@ mod tests {
         let (graph, pages) = do_test(Server::run().no_serve("/")).await;
         assert_eq!(graph.node_count(), 1);
         assert_eq!(pages.len(), 1);
+        assert_eq!(
+            pages.values().next().unwrap().result,
+            Err(FetchError::Status(StatusCode::BAD_REQUEST))
+        );
     }
 
-    async fn do_test(server: Server) -> (DiGraph<Url, ()>, HashMap<Url, Result<String, String>>) {
-        build_graph(
-            &Default::default(),
-            server
-                .url("/")
-                .to_string()
-                .parse()
-                .expect("URI isn't a URL"),
+    #[tokio::test]
+    async fn gzipped_body() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(LINK_TO_FOO.as_bytes()).unwrap();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/")).respond_with(
+                status_code(200)
+                    .append_header("Content-Encoding", "gzip")
+                    .body(encoder.finish().unwrap()),
+            ),
+        );
+        let server = server.no_serve("/foo");
+        let result = build_graph(
+            &CrawlConfig::default(),
+            server.url_str("/").parse().unwrap(),
             get_all_children,
         )
-        .await
+        .await;
+        assert_eq!(result.graph.node_count(), 2);
+        assert_eq!(
+            result.pages[&result.graph[result.root_index]].body(),
+            Ok(LINK_TO_FOO)
+        );
     }
 
-    fn get_all_children(url: &Url, body: &str, _depth: usize) -> Option<HashSet<Url>> {
-        Some(
-            Soup::new(body)
-                .tag("a")
-                .attr_name("href")
-                .find_all()
-                .map(|anchor| {
-                    let href = anchor.get("href").expect("Already filtered by href");
-                    match href.parse::<Url>() {
-                        Ok(url) => Ok(url),
-                        Err(url::ParseError::RelativeUrlWithoutBase) => url.join(&href),
-                        Err(e) => Err(e),
-                    }
-                })
-                .filter_map(Result::ok)
-                .collect(),
+    #[tokio::test]
+    async fn undecodable_body() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/")).respond_with(
+                status_code(200)
+                    .append_header("Content-Encoding", "gzip")
+                    .body(LINK_TO_FOO),
+            ),
+        );
+        let url = server.url_str("/").parse().unwrap();
+        let result = build_graph(&CrawlConfig::default(), url, get_all_children).await;
+        let error = result.pages[&result.graph[result.root_index]]
+            .result
+            .clone()
+            .unwrap_err();
+        assert!(matches!(error, FetchError::Decode(_)), "{error:?}");
+        assert_eq!(error.category(), "decode");
+    }
+
+    #[tokio::test]
+    async fn redirects_at_limit() {
+        let server = Server::run()
+            .redirect("/", "/1")
+            .redirect("/1", "/2")
+            .serve("/2", "");
+        let result = build_graph(
+            &CrawlConfig {
+                max_redirects: 2,
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 3);
+        assert_eq!(
+            result.pages[&result.graph[result.root_index]].body(),
+            Ok("")
+        );
+        assert_eq!(edge_kinds(&result.graph), [EdgeKind::Redirect; 2]);
+    }
+
+    #[tokio::test]
+    async fn redirects_over_limit() {
+        let server = Server::run()
+            .redirect("/", "/1")
+            .redirect("/1", "/2")
+            .redirect("/2", "/3");
+        let result = build_graph(
+            &CrawlConfig {
+                max_redirects: 2,
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
         )
+        .await;
+        assert_eq!(result.pages.len(), 1);
+        assert_eq!(
+            result.pages[&result.graph[result.root_index]].result,
+            Err(FetchError::RedirectLimitExceeded { limit: 2 })
+        );
     }
 
-    trait ServerExt {
-        fn serve(self, path: &'static str, body: &'static str) -> Self;
-        fn no_serve(self, path: &'static str) -> Self;
+    #[tokio::test]
+    async fn depth_first() {
+        assert_eq!(
+            fetch_order(Strategy::DepthFirst).await,
+            ["/", "/a", "/c", "/b"]
+        );
     }
 
-    impl ServerExt for Server {
-        fn serve(self, path: &'static str, body: &'static str) -> Self {
-            self.expect(
-                Expectation::matching(request::method_path("GET", path))
-                    .respond_with(status_code(200).body(body)),
-            );
-            self
-        }
+    #[tokio::test]
+    async fn breadth_first() {
+        assert_eq!(
+            fetch_order(Strategy::BreadthFirst).await,
+            ["/", "/a", "/b", "/c"]
+        );
+    }
 
-        fn no_serve(self, path: &'static str) -> Self {
-            self.expect(
-                Expectation::matching(request::method_path("GET", path))
-                    .respond_with(status_code(400)),
+    async fn fetch_order(strategy: Strategy) -> Vec<String> {
+        let server = Server::run()
+            .serve("/", r#"<a href="/a">a</a><a href="/b">b</a>"#)
+            .serve("/a", r#"<a href="/c">c</a>"#)
+            .serve("/b", "")
+            .serve("/c", "");
+        let order = Rc::new(RefCell::new(Vec::new()));
+        build_graph(
+            &CrawlConfig {
+                strategy,
+                max_concurrency: 1,
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            {
+                let order = order.clone();
+                move |response, depth| {
+                    order
+                        .borrow_mut()
+                        .push(response.final_url.path().to_owned());
+                    get_all_children(response, depth)
+                }
+            },
+        )
+        .await;
+        order.take()
+    }
+
+    #[tokio::test]
+    async fn should_keep() {
+        let server = Server::run().serve("/", LINK_TO_FOO).serve(
+            "/foo",
+            r#"<meta name="robots" content="nofollow"><a href="/bar">bar</a>"#,
+        );
+        let result = build_graph(
+            &CrawlConfig {
+                should_keep: Some(Arc::new(|_, body| !body.contains("nofollow"))),
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.graph.node_count(), 2);
+        assert!(result
+            .pages
+            .contains_key(&server.url_str("/foo").parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn truncated_body() {
+        let server = Server::run().no_serve("/foo");
+        let body = format!(r#"<a href="{}">foo</a>"#, server.url_str("/foo"));
+        let url = truncated_server(body.clone()).await;
+        let result = build_graph(&Default::default(), url.clone(), get_all_children).await;
+        assert_eq!(result.graph.node_count(), 2);
+        assert!(matches!(
+            &result.pages[&url].result,
+            Err(FetchError::PartialBody { bytes, .. }) if bytes == body.as_bytes()
+        ));
+    }
+
+    #[tokio::test]
+    async fn body_read_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap())
+            .parse::<Url>()
+            .unwrap();
+        // a byte every 100ms, for 10s
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _ = stream.read(&mut [0; 1024]).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n")
+                .await
+                .unwrap();
+            for _ in 0..100 {
+                if stream.write_all(b"x").await.is_err() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        });
+        let start = Instant::now();
+        let result = build_graph(
+            &CrawlConfig {
+                body_read_timeout: Some(Duration::from_millis(500)),
+                ..Default::default()
+            },
+            url.clone(),
+            get_all_children,
+        )
+        .await;
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(matches!(
+            &result.pages[&url].result,
+            Err(FetchError::BodyTimeout { bytes }) if !bytes.is_empty() && bytes.len() < 100
+        ));
+    }
+
+    #[tokio::test]
+    async fn connect_timeout() {
+        // a listener that never accepts, with its backlog full, so connecting
+        // to it hangs
+        let socket = tokio::net::TcpSocket::new_v4().unwrap();
+        socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let listener = socket.listen(1).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut backlog = Vec::new();
+        while let Ok(Ok(stream)) = tokio::time::timeout(
+            Duration::from_millis(100),
+            tokio::net::TcpStream::connect(addr),
+        )
+        .await
+        {
+            backlog.push(stream);
+        }
+        let hanging = Url::parse(&format!("http://{addr}/")).unwrap();
+        let refused = Url::parse("http://127.0.0.1:1/").unwrap();
+        let start = Instant::now();
+        let result = build_graph_from_seeds(
+            &CrawlConfig {
+                connect_timeout: Some(Duration::from_millis(200)),
+                body_read_timeout: Some(Duration::from_secs(60)),
+                ..Default::default()
+            },
+            [hanging.clone(), refused.clone()],
+            get_all_children,
+        )
+        .await;
+        // the hanging connection was given up on, not refused
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(
+            matches!(
+                result.pages[&hanging].result,
+                Err(FetchError::Connect(_) | FetchError::Timeout(_))
+            ),
+            "{:?}",
+            result.pages[&hanging].result
+        );
+        assert!(matches!(
+            result.pages[&refused].result,
+            Err(FetchError::Connect(_))
+        ));
+    }
+
+    /// Replies to a single request with `body`, claiming that it's twice as long.
+    async fn truncated_server(body: String) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _ = stream.read(&mut [0; 1024]).await.unwrap();
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
+                body.len() * 2
+            );
+            stream.write_all(head.as_bytes()).await.unwrap();
+            stream.write_all(body.as_bytes()).await.unwrap();
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn metadata() {
+        let server = Server::run()
+            .serve("/", LINK_TO_FOO)
+            .serve("/foo", "<article></article>");
+        let result = build_graph_with_metadata(
+            &Default::default(),
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+            |_, body| body.contains("<article>").then_some("article"),
+        )
+        .await;
+        assert_eq!(
+            result.pages[&server.url_str("/foo").parse().unwrap()].metadata,
+            Some("article")
+        );
+        assert_eq!(
+            result.pages[&result.graph[result.root_index]].metadata,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn cancelled_after_root() {
+        let server = Server::run().serve("/", LINK_TO_FOO);
+        let config = CrawlConfig::default();
+        let result = build_graph(&config, server.url_str("/").parse().unwrap(), {
+            let cancellation = config.cancellation.clone();
+            move |response, depth| {
+                cancellation.cancel();
+                get_all_children(response, depth)
+            }
+        })
+        .await;
+        assert_eq!(result.graph.node_count(), 1);
+        assert_eq!(result.graph.edge_count(), 0);
+        assert!(result.pages[&result.graph[result.root_index]]
+            .result
+            .is_ok());
+        assert_eq!(
+            result.frontier,
+            HashSet::from([server.url_str("/foo").parse().unwrap()])
+        );
+    }
+
+    #[tokio::test]
+    async fn cancelled_fetching_robots() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/robots.txt"))
+                .respond_with(delay_and_then(Duration::from_secs(10), status_code(200))),
+        );
+        let config = CrawlConfig {
+            respect_robots: true,
+            ..Default::default()
+        };
+        tokio::spawn({
+            let cancellation = config.cancellation.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                cancellation.cancel();
+            }
+        });
+        let start = Instant::now();
+        let result = build_graph(
+            &config,
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            result.pages[&result.graph[result.root_index]].result,
+            Err(FetchError::Cancelled)
+        );
+    }
+
+    #[tokio::test]
+    async fn host_client() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/"),
+                request::headers(contains(("user-agent", "override"))),
+            ])
+            .respond_with(status_code(200)),
+        );
+        let url = server.url_str("/").parse::<Url>().unwrap();
+        let client = CrawlConfig {
+            user_agent: Some(String::from("override")),
+            ..Default::default()
+        }
+        .build_client()
+        .unwrap();
+        let result = build_graph(
+            &CrawlConfig {
+                host_clients: HashMap::from([(url.host_str().unwrap().to_owned(), client)]),
+                ..Default::default()
+            },
+            url,
+            get_all_children,
+        )
+        .await;
+        assert!(result.pages[&result.graph[result.root_index]]
+            .result
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn content_type_extractor() {
+        let server = Server::run().no_serve("/foo");
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/sitemap.xml")).respond_with(
+                status_code(200)
+                    .append_header("Content-Type", "application/xml; charset=utf-8")
+                    .body("<urlset><url><loc>/foo</loc></url></urlset>"),
+            ),
+        );
+        let sitemap: ContentExtractor = Arc::new(|response, _| {
+            let (_, loc) = response.body.split_once("<loc>")?;
+            let (loc, _) = loc.split_once("</loc>")?;
+            Some(HashSet::from([response.final_url.join(loc).ok()?]))
+        });
+        let result = build_graph(
+            &CrawlConfig {
+                extractors: HashMap::from([(String::from("application/xml"), sitemap)]),
+                ..Default::default()
+            },
+            server.url_str("/sitemap.xml").parse().unwrap(),
+            |_, _| None,
+        )
+        .await;
+        assert_eq!(result.graph.node_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn canonical() {
+        let server = Server::run()
+            .serve(
+                "/",
+                r#"<link rel="canonical" href="/home"><a href="/foo">foo</a>"#,
+            )
+            .serve("/home", "")
+            .no_serve("/foo");
+        let result = build_graph(
+            &Default::default(),
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        let mut kinds = edge_kinds(&result.graph);
+        kinds.sort_by_key(|kind| *kind as u8);
+        assert_eq!(kinds, [EdgeKind::Link, EdgeKind::Canonical]);
+    }
+
+    #[tokio::test]
+    async fn merge_canonicals() {
+        let server = Server::run()
+            .serve(
+                "/",
+                r#"<link rel="canonical" href="/home"><a href="/a">a</a><a href="/b">b</a>"#,
+            )
+            .serve("/home", "")
+            .serve(
+                "/a",
+                r#"<link rel="canonical" href="/b"><a href="/b">b</a><a href="/c">c</a>"#,
+            )
+            .serve("/b", r#"<a href="/c">c</a>"#)
+            .serve("/c", "");
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let result = build_graph(
+            &CrawlConfig {
+                merge_canonicals: true,
+                count_links: true,
+                ..Default::default()
+            },
+            url("/"),
+            get_all_children,
+        )
+        .await;
+        let mut pages = Vec::from_iter(result.pages.keys().map(Url::path));
+        pages.sort();
+        assert_eq!(pages, ["/b", "/c", "/home"]);
+        assert_eq!(result.graph[result.root_index], url("/home"));
+        assert_eq!(result.pages[&url("/b")].discovered_as, [url("/a")]);
+        let mut edges = Vec::from_iter(result.graph.edge_references().map(|edge| {
+            (
+                result.graph[edge.source()].path(),
+                result.graph[edge.target()].path(),
+                edge.weight().kind,
+            )
+        }));
+        edges.sort_by_key(|(from, to, _)| (*from, *to));
+        assert_eq!(
+            edges,
+            [
+                ("/b", "/c", EdgeKind::Link),
+                ("/home", "/b", EdgeKind::Link)
+            ]
+        );
+    }
+
+    fn edge_kinds(graph: &DiGraph<Url, Edge>) -> Vec<EdgeKind> {
+        graph.edge_weights().map(|edge| edge.kind).collect()
+    }
+
+    #[tokio::test]
+    async fn nearest_seed() {
+        let server = Server::run()
+            .serve("/a", r#"<a href="/x">x</a>"#)
+            .serve("/x", r#"<a href="/y">y</a>"#)
+            .serve("/y", r#"<a href="/z">z</a>"#)
+            .serve("/b", r#"<a href="/y">y</a>"#)
+            .serve("/z", "");
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        // so that /y is first reached from /a, and again from /b
+        let result = build_graph_from_seeds(
+            &CrawlConfig {
+                strategy: Strategy::DepthFirst,
+                max_concurrency: 1,
+                max_depth: Some(2),
+                ..Default::default()
+            },
+            [url("/a"), url("/b")],
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.seed_indices.len(), 2);
+        assert_eq!(result.pages[&url("/y")].depth, 1);
+        assert_eq!(result.pages[&url("/z")].depth, 2);
+        assert!(result.frontier.is_empty());
+    }
+
+    #[tokio::test]
+    async fn get() {
+        let server = Server::run()
+            .serve("/", r#"<a href="/foo#top">foo</a><a href="/bar/">bar</a>"#)
+            .serve("/foo", "")
+            .serve("/bar/", "");
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let result = build_graph(&Default::default(), url("/"), get_all_children).await;
+        assert_eq!(result.pages.len(), 3);
+        assert!(result.pages.contains_key(&url("/foo")));
+        assert!(result.contains(&url("/foo#bottom")));
+        assert!(result.contains(&url("/foo/")));
+        assert!(result.contains(&url("/bar")));
+        assert!(result.get(&url("/")).unwrap().result.is_ok());
+        assert!(!result.contains(&url("/baz")));
+    }
+
+    #[tokio::test]
+    async fn no_duplicate_fetches() {
+        // every page links to every other, so most are discovered again while
+        // they're still being fetched
+        const ALL: &str = r#"<a href="/">root</a><a href="/a">a</a><a href="/b">b</a><a href="/c">c</a><a href="/d">d</a>"#;
+        for strategy in [Strategy::Concurrent, Strategy::BreadthFirst] {
+            let server = Server::run();
+            for path in ["/", "/a", "/b", "/c", "/d"] {
+                server.expect(
+                    Expectation::matching(request::method_path("GET", path))
+                        .times(1)
+                        .respond_with(delay_and_then(
+                            Duration::from_millis(50),
+                            status_code(200).body(ALL),
+                        )),
+                );
+            }
+            let fetches = Arc::new(std::sync::Mutex::new(HashMap::<Url, usize>::new()));
+            let result = build_graph(
+                &CrawlConfig {
+                    strategy,
+                    on_fetch: Some(Arc::new({
+                        let fetches = fetches.clone();
+                        move |url| *fetches.lock().unwrap().entry(url.clone()).or_default() += 1
+                    })),
+                    ..Default::default()
+                },
+                server.url_str("/").parse().unwrap(),
+                get_all_children,
+            )
+            .await;
+            assert_eq!(result.pages.len(), 5);
+            let fetches = fetches.lock().unwrap();
+            assert_eq!(fetches.len(), 5);
+            assert!(fetches.values().all(|count| *count == 1));
+        }
+    }
+
+    #[tokio::test]
+    async fn nearer_while_in_flight() {
+        // /p is reached at depth 3 through /a and /b, then at depth 2 through
+        // /s while it's still being fetched
+        let server = Server::run()
+            .serve("/", r#"<a href="/a">a</a><a href="/s">s</a>"#)
+            .serve("/a", r#"<a href="/b">b</a>"#)
+            .serve("/b", r#"<a href="/p">p</a>"#)
+            .serve("/q", "");
+        for (path, delay, body) in [
+            ("/s", 100, r#"<a href="/p">p</a>"#),
+            ("/p", 300, r#"<a href="/q">q</a>"#),
+        ] {
+            server.expect(
+                Expectation::matching(request::method_path("GET", path)).respond_with(
+                    delay_and_then(Duration::from_millis(delay), status_code(200).body(body)),
+                ),
+            );
+        }
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let result = build_graph(
+            &CrawlConfig {
+                max_depth: Some(3),
+                ..Default::default()
+            },
+            url("/"),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages[&url("/p")].depth, 2);
+        assert_eq!(result.pages[&url("/q")].depth, 3);
+    }
+
+    #[tokio::test]
+    async fn max_concurrency() {
+        let paths = ["/a", "/b", "/c", "/d", "/e", "/f"];
+        let links = String::from_iter(paths.map(|path| format!(r#"<a href="{path}">x</a>"#)));
+        let server = Server::run().serve("/", links.leak());
+        for path in paths {
+            server.expect(
+                Expectation::matching(request::method_path("GET", path))
+                    .respond_with(delay_and_then(Duration::from_millis(100), status_code(200))),
+            );
+        }
+        let start = Instant::now();
+        let result = build_graph(
+            &CrawlConfig {
+                strategy: Strategy::Concurrent,
+                max_concurrency: 2,
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 7);
+        // two at a time, so three times as long as one
+        assert!(start.elapsed() >= Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn per_host_concurrency() {
+        let server = Server::run().serve(
+            "/",
+            r#"<a href="/a">a</a><a href="/b">b</a><a href="/c">c</a><a href="/d">d</a>"#,
+        );
+        for path in ["/a", "/b", "/c", "/d"] {
+            server.expect(
+                Expectation::matching(request::method_path("GET", path))
+                    .respond_with(delay_and_then(Duration::from_millis(100), status_code(200))),
+            );
+        }
+        let start = Instant::now();
+        let result = build_graph(
+            &CrawlConfig {
+                strategy: Strategy::Concurrent,
+                per_host_concurrency: Some(2),
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 5);
+        // two at a time, so twice as long as one
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn seed_body() {
+        // the root isn't served, so fetching it would fail the test
+        let server = Server::run()
+            .serve("/foo", LINK_TO_BAR)
+            .serve("/bar", LINK_TO_FOO);
+        let root = server.url_str("/").parse::<Url>().unwrap();
+        let result = build_graph_from_seed_body(
+            &Default::default(),
+            root.clone(),
+            LINK_TO_FOO.into(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 3);
+        assert_eq!(result.pages[&root].body(), Ok(LINK_TO_FOO));
+        assert_eq!(result.graph.edge_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn render_fallback() {
+        let server = Server::run().serve("/", "").serve("/foo", "");
+        let result = build_graph(
+            &CrawlConfig {
+                render_fallback: Some(Arc::new(|url| {
+                    let rendered = (url.path() == "/").then(|| LINK_TO_FOO.to_owned());
+                    Box::pin(async move { rendered })
+                })),
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 2);
+        // the fetched body is kept
+        assert_eq!(
+            result.pages[&server.url_str("/").parse().unwrap()].body(),
+            Ok("")
+        );
+    }
+
+    #[tokio::test]
+    async fn preprocess_body() {
+        let server = Server::run()
+            .serve("/", r#"{"html": "<a href='/foo'>foo</a>"}"#)
+            .serve("/foo", "");
+        let result = build_graph(
+            &CrawlConfig {
+                preprocess_body: Some(Arc::new(|_, body| {
+                    let envelope = serde_json::from_str::<HashMap<String, String>>(&body);
+                    envelope.map_or(body, |mut it| it.remove("html").unwrap_or_default())
+                })),
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 2);
+        // the fetched body is kept
+        assert!(result.pages[&server.url_str("/").parse().unwrap()]
+            .body()
+            .unwrap()
+            .starts_with('{'));
+    }
+
+    #[tokio::test]
+    async fn read_files() {
+        let dir = std::env::temp_dir().join(format!("sprawl-files-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(
+            dir.join("index.html"),
+            r#"<a href="a.html">a</a><a href="sub/">sub</a>"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("a.html"), r#"<a href="missing.html">missing</a>"#).unwrap();
+        std::fs::write(dir.join("sub/index.html"), r#"<a href="../a.html">a</a>"#).unwrap();
+        let root = Url::from_directory_path(&dir).unwrap();
+        let result = build_graph(
+            &CrawlConfig {
+                read_files: true,
+                ..Default::default()
+            },
+            root.clone(),
+            get_all_children,
+        )
+        .await;
+        std::fs::remove_dir_all(&dir).unwrap();
+        let ok = |path: &str| result.pages[&root.join(path).unwrap()].result.is_ok();
+        assert!(ok("") && ok("a.html") && ok("sub/"));
+        assert!(!ok("missing.html"));
+        assert_eq!(result.pages.len(), 4);
+        assert_eq!(result.graph.edge_count(), 4);
+    }
+
+    #[tokio::test]
+    async fn root_failed() {
+        let server = Server::run().serve("/foo", "");
+        // nothing listens on port 1
+        let unreachable = Url::parse("http://127.0.0.1:1/").unwrap();
+        let result = build_graph_from_seeds(
+            &Default::default(),
+            [unreachable.clone(), server.url_str("/foo").parse().unwrap()],
+            get_all_children,
+        )
+        .await;
+        assert!(result.root_failed());
+        assert_eq!(
+            Vec::from_iter(result.failed_seeds().map(|(url, _)| url)),
+            [&unreachable]
+        );
+    }
+
+    #[tokio::test]
+    async fn max_url_length() {
+        let mega = format!("/?q={}", "a".repeat(5000));
+        let body = format!(r#"<a href="{mega}">mega</a>{LINK_TO_FOO}"#);
+        // the mega url isn't served, so fetching it would fail the test
+        let server = Server::run().serve("/foo", "");
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/"))
+                .respond_with(status_code(200).body(body)),
+        );
+        let result = build_graph(
+            &CrawlConfig {
+                max_url_length: Some(2000),
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 3);
+        assert_eq!(
+            result.pages[&server.url_str(&mega).parse().unwrap()].result,
+            Err(FetchError::Skipped(SkipReason::UrlTooLong { limit: 2000 }))
+        );
+    }
+
+    #[tokio::test]
+    async fn respect_robots() {
+        // /foo isn't served, so fetching it would fail the test
+        let server = Server::run()
+            .serve("/robots.txt", "User-agent: *\nDisallow: /foo\n")
+            .serve("/", r#"<a href="/foo">foo</a><a href="/bar">bar</a>"#)
+            .serve("/bar", LINK_TO_FOO);
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let result = build_graph(
+            &CrawlConfig {
+                respect_robots: true,
+                ..Default::default()
+            },
+            url("/"),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 3);
+        assert_eq!(
+            result.pages[&url("/foo")].result,
+            Err(FetchError::Skipped(SkipReason::Robots))
+        );
+        assert!(!result.pages.contains_key(&url("/robots.txt")));
+    }
+
+    #[tokio::test]
+    async fn crawl_delay() {
+        let server = Server::run()
+            .serve("/robots.txt", "User-agent: *\nCrawl-delay: 0.3\n")
+            .serve("/", LINK_TO_FOO)
+            .serve("/foo", "");
+        let start = Instant::now();
+        let result = build_graph(
+            &CrawlConfig {
+                respect_robots: true,
+                per_host_delay: Some(Duration::from_millis(10)),
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 2);
+        // the longer delay wins
+        assert!(start.elapsed() >= Duration::from_millis(300));
+    }
+
+    #[test]
+    fn robots_rules() {
+        let body = "\
+User-agent: *
+Disallow: /
+Crawl-delay: 2
+
+User-agent: other
+User-agent: sprawl # us
+Disallow: /private
+Allow: /private/public
+Disallow: /*.pdf$
+Crawl-delay: 0.5
+";
+        let robots = crate::robots::Robots::parse(body, Some("sprawl/1.0"));
+        let allows = |path| {
+            robots.allows(
+                &Url::parse("http://example.test")
+                    .unwrap()
+                    .join(path)
+                    .unwrap(),
+            )
+        };
+        assert!(allows("/"));
+        assert!(!allows("/private/page"));
+        assert!(allows("/private/public/page"));
+        assert!(!allows("/docs/a.pdf"));
+        assert!(allows("/docs/a.pdf?page=2"));
+        assert_eq!(robots.crawl_delay, Some(Duration::from_millis(500)));
+        let robots = crate::robots::Robots::parse(body, None);
+        assert!(!robots.allows(&Url::parse("http://example.test/page").unwrap()));
+        assert_eq!(robots.crawl_delay, Some(Duration::from_secs(2)));
+    }
+
+    #[tokio::test]
+    async fn path_traps() {
+        // each page links one level deeper, forever
+        let server = Server::run()
+            .serve("/", r#"<a href="a/">a</a>"#)
+            .serve("/a/", r#"<a href="a/">a</a>"#)
+            .serve("/a/a/", r#"<a href="a/">a</a><a href="/b/c/d/e/">e</a>"#);
+        let result = build_graph(
+            &CrawlConfig {
+                max_segment_repeats: Some(2),
+                max_path_segments: Some(3),
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 5);
+        assert_eq!(
+            result.pages[&server.url_str("/a/a/a/").parse().unwrap()].result,
+            Err(FetchError::Skipped(SkipReason::RepeatedPathSegment {
+                segment: "a".into(),
+                limit: 2
+            }))
+        );
+        assert_eq!(
+            result.pages[&server.url_str("/b/c/d/e/").parse().unwrap()].result,
+            Err(FetchError::Skipped(SkipReason::TooManyPathSegments {
+                limit: 3
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn events() {
+        let server = Server::run()
+            .serve("/", LINK_TO_FOO)
+            .redirect("/foo", "/bar")
+            .serve("/bar", r#"<a href="/baz">baz</a>"#)
+            .serve("/baz", LINK_TO_FOO);
+        let mut config = CrawlConfig {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        let events = config.event_stream();
+        let result = build_graph(
+            &config,
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        drop(config);
+        let events = events.collect::<Vec<_>>().await;
+        let (mut started, mut nodes, mut edges) = (HashSet::new(), HashSet::new(), HashSet::new());
+        for event in &events {
+            match event {
+                CrawlEvent::FetchStarted { url } => assert!(started.insert(url.clone())),
+                CrawlEvent::NodeFetched { url, .. } => assert!(nodes.insert(url.clone())),
+                CrawlEvent::EdgeDiscovered { from, to, kind } => {
+                    assert!(edges.insert((from.clone(), to.clone(), *kind)))
+                }
+                CrawlEvent::DepthLimited { url, depth } => {
+                    assert_eq!((url.path(), *depth), ("/baz", 2))
+                }
+                CrawlEvent::Finished(_) => {}
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+        assert_eq!(nodes, HashSet::from_iter(result.pages.keys().cloned()));
+        // `/bar` is a redirect hop, fetched as part of `/foo`
+        assert_eq!(
+            started,
+            HashSet::from_iter(
+                ["/", "/foo", "/baz"].map(|path| server.url_str(path).parse().unwrap())
+            )
+        );
+        assert_eq!(edges.len(), result.graph.edge_count());
+        assert_eq!(
+            events.last(),
+            Some(&CrawlEvent::Finished(result.stats.clone()))
+        );
+        assert_eq!(result.stats.pages, 4);
+        assert_eq!(
+            result.stats.depth_histogram,
+            BTreeMap::from_iter([(0, 1), (1, 2), (2, 1)])
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn build_graph_parallel() {
+        let server = Server::run()
+            .serve("/", LINK_TO_FOO)
+            .serve("/foo", LINK_TO_BAR)
+            .serve("/bar", LINK_TO_FOO);
+        let root = server.url_str("/").parse::<Url>().unwrap();
+        // spawned, to check that it's `Send`
+        let result = tokio::spawn(async move {
+            crate::build_graph_parallel(&Default::default(), root, get_all_children).await
+        })
+        .await
+        .unwrap();
+        assert_eq!(result.pages.len(), 3);
+        assert!(result.pages.values().all(|page| page.result.is_ok()));
+        assert_eq!(result.graph.edge_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn crawl_stream() {
+        let server = Server::run()
+            .serve("/", LINK_TO_FOO)
+            .serve("/foo", LINK_TO_BAR)
+            .no_serve("/bar");
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let events = crate::crawl_stream(&Default::default(), url("/"), get_all_children)
+            .collect::<Vec<_>>()
+            .await;
+        let fetched = Vec::from_iter(events.iter().filter_map(|event| {
+            match event {
+                CrawlEvent::NodeFetched { url, result, .. } => Some((
+                    url.path(),
+                    result
+                        .as_ref()
+                        .map(|it| it.body.as_str())
+                        .map_err(Clone::clone),
+                )),
+                _ => None,
+            }
+        }));
+        assert_eq!(
+            fetched,
+            [
+                ("/", Ok(LINK_TO_FOO)),
+                ("/foo", Ok(LINK_TO_BAR)),
+                ("/bar", Err(FetchError::Status(StatusCode::BAD_REQUEST)))
+            ]
+        );
+        assert!(events.iter().any(|event| matches!(
+            event,
+            CrawlEvent::Error { url, error: FetchError::Status(StatusCode::BAD_REQUEST) } if url.path() == "/bar"
+        )));
+        assert!(matches!(
+            events.last(),
+            Some(CrawlEvent::Finished(stats)) if stats.pages == 3
+        ));
+    }
+
+    #[tokio::test]
+    async fn collect_graph() {
+        let server = Server::run()
+            .serve("/", LINK_TO_FOO)
+            .redirect("/foo", "/bar")
+            .serve("/bar", r#"<a href="/baz">baz</a>"#)
+            .no_serve("/baz");
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let (graph, pages) = crate::collect_graph(crate::crawl_stream(
+            &Default::default(),
+            url("/"),
+            get_all_children,
+        ))
+        .await;
+        assert_eq!(pages.len(), 4);
+        assert_eq!(
+            pages[&url("/bar")].as_ref().map(|it| it.body.as_str()),
+            Ok(r#"<a href="/baz">baz</a>"#)
+        );
+        assert_eq!(
+            pages[&url("/baz")],
+            Err(FetchError::Status(StatusCode::BAD_REQUEST))
+        );
+        // the same graph `build_graph` returns for this site
+        let edges = HashSet::<_>::from_iter(graph.edge_indices().map(|edge| {
+            let (from, to) = graph.edge_endpoints(edge).unwrap();
+            (graph[from].path(), graph[to].path(), graph[edge])
+        }));
+        assert_eq!(
+            edges,
+            HashSet::from_iter([
+                ("/", "/foo", EdgeKind::Link),
+                ("/foo", "/bar", EdgeKind::Redirect),
+                ("/bar", "/baz", EdgeKind::Link),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn events_while_crawling() {
+        let server = Server::run()
+            .serve("/", LINK_TO_FOO)
+            .serve("/foo", LINK_TO_BAR)
+            .serve("/bar", "");
+        let mut config = CrawlConfig::default();
+        let events = config.event_stream();
+        let fetched = std::sync::Mutex::new(Vec::new());
+        let crawl = async move {
+            let result = build_graph(
+                &config,
+                server.url_str("/").parse().unwrap(),
+                get_all_children,
+            )
+            .await;
+            drop(config);
+            result
+        };
+        let consume = events
+            .filter_map(|event| async move {
+                match event {
+                    CrawlEvent::NodeFetched { url, .. } => Some(url),
+                    _ => None,
+                }
+            })
+            .for_each_concurrent(None, |url| {
+                let fetched = &fetched;
+                async move { fetched.lock().unwrap().push(url) }
+            });
+        let (result, ()) = tokio::join!(crawl, consume);
+        let mut fetched = fetched.into_inner().unwrap();
+        fetched.sort();
+        let mut pages = Vec::from_iter(result.pages.into_keys());
+        pages.sort();
+        assert_eq!(fetched, pages);
+    }
+
+    #[tokio::test]
+    async fn scope_prefix() {
+        for prefix in ["/v2", "/v2/"] {
+            // the out of scope pages aren't served, so fetching them would fail the test
+            let server = Server::run()
+                .serve(
+                    "/v2/",
+                    r#"<a href="/v2">v2</a><a href="/v2/docs">docs</a><a href="/v2beta">beta</a><a href="/">root</a>"#,
+                )
+                .serve("/v2", "")
+                .serve("/v2/docs", "");
+            let url = |path| server.url_str(path).parse::<Url>().unwrap();
+            let result = build_graph(
+                &CrawlConfig {
+                    scope_prefix: Some(url(prefix)),
+                    ..Default::default()
+                },
+                url("/v2/"),
+                get_all_children,
+            )
+            .await;
+            assert_eq!(result.pages.len(), 5);
+            for out_of_scope in ["/v2beta", "/"] {
+                assert_eq!(
+                    result.pages[&url(out_of_scope)].result,
+                    Err(FetchError::Skipped(SkipReason::OutOfScope))
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn max_bytes_per_sec() {
+        let server = Server::run();
+        for (path, links) in [
+            ("/", r#"<a href="/a">a</a><a href="/b">b</a>"#),
+            ("/a", ""),
+            ("/b", ""),
+        ] {
+            let body = format!("{links}{}", "x".repeat(50_000 - links.len()));
+            server.expect(
+                Expectation::matching(request::method_path("GET", path))
+                    .respond_with(status_code(200).body(body)),
+            );
+        }
+        let start = Instant::now();
+        let result = build_graph(
+            &CrawlConfig {
+                max_bytes_per_sec: Some(100_000),
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 3);
+        // the first second's worth is free, the rest takes half a second
+        assert!(start.elapsed() >= Duration::from_millis(450));
+    }
+
+    #[tokio::test]
+    async fn throttled_within_body_read_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap())
+            .parse::<Url>()
+            .unwrap();
+        // half a second's worth over the throttle's first second, then the
+        // last byte a while later
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _ = stream.read(&mut [0; 1024]).await.unwrap();
+            let head = "HTTP/1.1 200 OK\r\nContent-Length: 150001\r\n\r\n";
+            stream.write_all(head.as_bytes()).await.unwrap();
+            stream.write_all(&[b'x'; 150_000]).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(700)).await;
+            stream.write_all(b"x").await.unwrap();
+        });
+        let result = build_graph(
+            &CrawlConfig {
+                max_bytes_per_sec: Some(100_000),
+                body_read_timeout: Some(Duration::from_millis(500)),
+                ..Default::default()
+            },
+            url.clone(),
+            get_all_children,
+        )
+        .await;
+        // waited on the server for less than the timeout, the rest was the
+        // throttle
+        assert_eq!(result.pages[&url].body().map(str::len), Ok(150_001));
+    }
+
+    #[tokio::test]
+    async fn shared_context() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/"))
+                .times(2)
+                .respond_with(status_code(200).body("x".repeat(100_000))),
+        );
+        let mut config = CrawlConfig {
+            max_bytes_per_sec: Some(100_000),
+            ..Default::default()
+        };
+        config.context = Some(CrawlContext::new(&config));
+        let root: Url = server.url_str("/").parse().unwrap();
+        let start = Instant::now();
+        build_graph(&config, root.clone(), get_all_children).await;
+        // the first crawl used up the first second's worth
+        assert!(start.elapsed() < Duration::from_millis(450));
+        build_graph(&config, root, get_all_children).await;
+        assert!(start.elapsed() >= Duration::from_millis(950));
+    }
+
+    #[tokio::test]
+    async fn shared_robots_and_delays() {
+        let server = Server::run().serve("/robots.txt", "User-agent: *\nCrawl-delay: 0.3\n");
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/"))
+                .times(2)
+                .respond_with(status_code(200)),
+        );
+        let mut config = CrawlConfig {
+            respect_robots: true,
+            ..Default::default()
+        };
+        config.context = Some(CrawlContext::new(&config));
+        let root: Url = server.url_str("/").parse().unwrap();
+        build_graph(&config, root.clone(), get_all_children).await;
+        // robots.txt isn't fetched again, and the second crawl waits for the
+        // first's delay
+        let start = Instant::now();
+        build_graph(&config, root.clone(), get_all_children).await;
+        assert!(start.elapsed() >= Duration::from_millis(250));
+    }
+
+    #[tokio::test]
+    async fn resolve() {
+        let server = Server::run().serve("/", LINK_TO_FOO).serve("/foo", "");
+        let addr = server.addr();
+        let result = build_graph(
+            &CrawlConfig {
+                resolve: vec![("staging.test".into(), addr)],
+                ..Default::default()
+            },
+            format!("http://staging.test:{}/", addr.port())
+                .parse()
+                .unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 2);
+        assert!(result.pages.values().all(|page| page.result.is_ok()));
+    }
+
+    #[tokio::test]
+    async fn per_host_delay() {
+        let a = Server::run().serve("/", LINK_TO_FOO).serve("/foo", "");
+        let b = Server::run().serve("/", LINK_TO_FOO).serve("/foo", "");
+        let start = Instant::now();
+        let result = build_graph_from_seeds(
+            &CrawlConfig {
+                per_host_delay: Some(Duration::from_millis(300)),
+                resolve: vec![("a.test".into(), a.addr()), ("b.test".into(), b.addr())],
+                ..Default::default()
+            },
+            [
+                format!("http://a.test:{}/", a.addr().port())
+                    .parse()
+                    .unwrap(),
+                format!("http://b.test:{}/", b.addr().port())
+                    .parse()
+                    .unwrap(),
+            ],
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 4);
+        // each host waited once, at the same time as the other
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(300));
+        assert!(elapsed < Duration::from_millis(600));
+    }
+
+    #[test]
+    fn mermaid() {
+        let mut graph = DiGraph::new();
+        let root = graph.add_node(Url::parse("http://example.com/").unwrap());
+        let foo = graph.add_node(Url::parse(r#"http://example.com/"foo""#).unwrap());
+        graph.add_edge(
+            root,
+            foo,
+            Edge {
+                kind: EdgeKind::Link,
+                count: 1,
+                attributes: Vec::new(),
+            },
+        );
+        graph.add_edge(
+            foo,
+            root,
+            Edge {
+                kind: EdgeKind::Redirect,
+                count: 1,
+                attributes: Vec::new(),
+            },
+        );
+        assert_eq!(
+            export::to_mermaid(&graph),
+            r#"graph TD
+    n0["http://example.com/"]
+    n1["http://example.com/%22foo%22"]
+    n0 --> n1
+    n1 -.-> n0
+"#
+        );
+    }
+
+    #[tokio::test]
+    async fn cache() {
+        let mut server = Server::run()
+            .serve("/", LINK_TO_FOO)
+            .serve("/foo", LINK_TO_BAR)
+            .serve("/bar", "");
+        let root = server.url_str("/").parse::<Url>().unwrap();
+        let mut pages = build_graph(&Default::default(), root.clone(), get_all_children)
+            .await
+            .pages;
+        server.verify_and_clear();
+        // everything but /foo is fresh, so /foo is the only page fetched again
+        let foo = server.url_str("/foo").parse().unwrap();
+        pages.get_mut(&foo).unwrap().fetched_at -= Duration::from_secs(2 * 60 * 60);
+        let _server = server.serve("/foo", LINK_TO_BAR);
+        let result = build_graph(
+            &CrawlConfig {
+                cache: Arc::new(pages),
+                cache_ttl: Duration::from_secs(60 * 60),
+                ..Default::default()
+            },
+            root,
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 3);
+        assert!(result.pages[&foo].fetched_at.elapsed().unwrap() < Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn cycles() {
+        let server = Server::run()
+            .serve("/", r#"<a href="/">root</a><a href="/foo">foo</a>"#)
+            .serve("/foo", LINK_TO_BAR)
+            .serve("/bar", r#"<a href="/foo">foo</a><a href="/baz">baz</a>"#)
+            .serve("/baz", "");
+        let (graph, _) = do_test(server).await;
+        let mut cycles = Vec::from_iter(
+            analysis::cycles(&graph)
+                .into_iter()
+                .map(|cycle| Vec::from_iter(cycle.into_iter().map(|url| url.path().to_owned()))),
+        );
+        for cycle in &mut cycles {
+            // which page comes first depends on the order of the graph
+            let first = cycle.iter().position(|it| it == "/foo").unwrap_or(0);
+            cycle.rotate_left(first);
+        }
+        cycles.sort();
+        assert_eq!(cycles, [vec!["/"], vec!["/foo", "/bar"]]);
+        assert!(!analysis::is_acyclic(&graph));
+    }
+
+    #[tokio::test]
+    async fn accept_language() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/"),
+                request::headers(contains(("accept-language", "fr-CH, fr;q=0.9"))),
+            ])
+            .respond_with(status_code(200)),
+        );
+        let result = build_graph(
+            &CrawlConfig {
+                accept_language: Some(HeaderValue::from_static("fr-CH, fr;q=0.9")),
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert!(result.pages.values().all(|page| page.result.is_ok()));
+    }
+
+    #[tokio::test]
+    async fn headers_with_own_client() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/"),
+                request::headers(contains(("user-agent", "sprawl-test"))),
+                request::headers(contains(("accept-language", "de"))),
+            ])
+            .respond_with(status_code(200)),
+        );
+        let result = build_graph(
+            &CrawlConfig {
+                client: Some(reqwest::Client::new()),
+                user_agent: Some(String::from("sprawl-test")),
+                accept_language: Some(HeaderValue::from_static("de")),
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert!(result.pages.values().all(|page| page.result.is_ok()));
+    }
+
+    #[tokio::test]
+    async fn edge_and_node_lists() {
+        let server = Server::run().serve("/", LINK_TO_FOO).serve("/foo", "");
+        let (graph, pages) = do_test(server).await;
+        let find = |path| pages.keys().find(|url| url.path() == path).unwrap();
+        let (root, foo) = (find("/"), find("/foo"));
+        assert_eq!(export::to_edge_list(&graph), format!("{root}\t{foo}\n"));
+        assert_eq!(
+            export::to_node_list(&pages),
+            format!("{root}\tok\n{foo}\tok\n")
+        );
+    }
+
+    #[tokio::test]
+    async fn sql() {
+        let server = Server::run()
+            .serve("/", r#"<a href="/it's">it's</a>"#)
+            .no_serve("/it's");
+        let root = server.url_str("/").parse::<Url>().unwrap();
+        let config = CrawlConfig {
+            link_attributes: true,
+            ..Default::default()
+        };
+        let result = build_graph(&config, root.clone(), get_all_children).await;
+        let sql = export::to_sql(&result);
+        assert!(sql.starts_with("BEGIN;\nCREATE TABLE nodes"));
+        assert!(sql.ends_with("COMMIT;\n"));
+        assert!(sql.contains(&format!(
+            "INSERT INTO nodes VALUES ('{root}', 200, 'ok', 0, 'c5ea2397df072cf3', 24);"
+        )));
+        assert!(sql.contains(&format!(
+            "INSERT INTO edges VALUES ('{root}', '{root}it''s', 'link', 1, 'it''s');"
+        )));
+        // failed pages are rows too
+        assert!(sql.contains(&format!(
+            "INSERT INTO nodes VALUES ('{root}it''s', 400, 'status 400', 1, NULL, NULL);"
+        )));
+    }
+
+    #[tokio::test]
+    async fn json_and_graphml() {
+        let server = Server::run()
+            .serve("/", r#"<a href="/a&b">a&amp;b</a>"#)
+            .no_serve("/a&b");
+        let root = server.url_str("/").parse::<Url>().unwrap();
+        let result = build_graph(&Default::default(), root.clone(), get_all_children).await;
+        let json = export::to_json(&result);
+        let nodes = json["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 2);
+        let index = |url: &str| nodes.iter().position(|it| it["url"] == url).unwrap();
+        let (from, to) = (index(root.as_str()), index(&format!("{root}a&b")));
+        assert_eq!(nodes[from]["status"], 200);
+        assert_eq!(nodes[from]["outcome"], "ok");
+        assert_eq!(nodes[to]["status"], 400);
+        assert_eq!(
+            json["edges"],
+            serde_json::json!([{"from": from, "to": to, "kind": "link", "count": 1}])
+        );
+        let graphml = export::to_graphml(&result);
+        assert!(graphml.contains(r#"<graph id="crawl" edgedefault="directed">"#));
+        assert!(graphml.contains(&format!(r#"<data key="url">{root}a&amp;b</data>"#)));
+        assert!(graphml.contains(&format!(r#"<edge source="n{from}" target="n{to}">"#)));
+        assert!(graphml.ends_with("</graphml>\n"));
+    }
+
+    #[tokio::test]
+    async fn status() {
+        let server = Server::run()
+            .serve(
+                "/",
+                r#"<a href="/missing">missing</a><a href="/down">down</a>"#,
+            )
+            .no_serve("/missing");
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/down")).respond_with(
+                status_code(503).body(r#"Down for <a href="/maintenance">maintenance</a>"#),
+            ),
+        );
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let result = build_graph(&Default::default(), url("/"), get_all_children).await;
+        let status = |path| result.pages[&url(path)].status();
+        assert_eq!(status("/"), Some(StatusCode::OK));
+        assert_eq!(status("/missing"), Some(StatusCode::BAD_REQUEST));
+        assert_eq!(status("/down"), Some(StatusCode::SERVICE_UNAVAILABLE));
+        assert_eq!(
+            result.pages[&url("/down")].result,
+            Err(FetchError::Status(StatusCode::SERVICE_UNAVAILABLE))
+        );
+        // kept, but not followed
+        let down = result.pages[&url("/down")].error_response.as_ref().unwrap();
+        assert_eq!(down.status, Some(StatusCode::SERVICE_UNAVAILABLE));
+        assert_eq!(
+            down.body,
+            r#"Down for <a href="/maintenance">maintenance</a>"#
+        );
+        assert!(!result.contains(&url("/maintenance")));
+        assert_eq!(result.pages[&url("/")].error_response, None);
+    }
+
+    #[tokio::test]
+    async fn request_hook() {
+        let server = Server::run();
+        for path in ["/", "/foo"] {
+            server.expect(
+                Expectation::matching(all_of![
+                    request::method_path("GET", path),
+                    request::headers(contains(("x-signature", path))),
+                ])
+                .respond_with(status_code(200).body(LINK_TO_FOO)),
+            );
+        }
+        let result = build_graph(
+            &CrawlConfig {
+                request_hook: Some(Arc::new(|url, request| {
+                    request.header("x-signature", url.path())
+                })),
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert!(result.pages.values().all(|page| page.result.is_ok()));
+    }
+
+    #[tokio::test]
+    async fn sitemap() {
+        let server = Server::run()
+            .serve(
+                "/",
+                r#"<a href="/foo?a&amp;b">foo</a><a href="/old">old</a><a href="http://127.0.0.1:1/">elsewhere</a>"#,
+            )
+            .redirect("/old", "/new")
+            .serve("/new", "");
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/foo")).respond_with(
+                status_code(200).append_header("Last-Modified", "Wed, 01 Jun 2022 12:30:00 GMT"),
+            ),
+        );
+        let root = server.url_str("/").parse::<Url>().unwrap();
+        let result = build_graph(&Default::default(), root.clone(), get_all_children).await;
+        assert_eq!(
+            export::to_sitemap(&result, &root),
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url>
+    <loc>{root}</loc>
+  </url>
+  <url>
+    <loc>{root}foo?a&amp;b</loc>
+    <lastmod>2022-06-01T12:30:00Z</lastmod>
+  </url>
+  <url>
+    <loc>{root}new</loc>
+  </url>
+</urlset>
+"#
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn referrers() {
+        let server = Server::run()
+            .serve("/", r#"<a href="/foo">foo</a><a href="/broken">broken</a>"#)
+            .serve(
+                "/foo",
+                r#"<a href="/broken">broken</a><a href="/docs/">docs</a>"#,
+            )
+            .serve("/broken", "")
+            .serve("/docs/", "");
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let result = build_graph(&Default::default(), url("/"), get_all_children).await;
+        assert_eq!(
+            analysis::referrers(&result, &url("/broken#top")),
+            [url("/"), url("/foo")]
+        );
+        assert_eq!(analysis::referrers(&result, &url("/")), []);
+        // with or without the trailing slash
+        assert_eq!(analysis::referrers(&result, &url("/docs")), [url("/foo")]);
+    }
+
+    #[tokio::test]
+    async fn lookups() {
+        let server = Server::run()
+            .serve("/", r#"<a href="/foo">foo</a><a href="/bar">bar</a>"#)
+            .serve("/foo", r#"<a href="/bar">bar</a>"#)
+            .serve("/bar", r#"<a href="/">home</a>"#);
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let result = build_graph(&Default::default(), url("/"), get_all_children).await;
+        let foo = result.node_by_url(&url("/foo/#top")).unwrap();
+        assert_eq!(result.graph[foo], url("/foo"));
+        assert_eq!(result.body_of(foo), Some(r#"<a href="/bar">bar</a>"#));
+        assert_eq!(result.node_by_url(&url("/missing")), None);
+        assert_eq!(result.neighbors_of(&url("/")), [&url("/bar"), &url("/foo")]);
+        assert_eq!(result.neighbors_of(&url("/missing")), Vec::<&Url>::new());
+        assert_eq!(
+            analysis::in_degree_ranking(&result),
+            [(url("/bar"), 2), (url("/"), 1), (url("/foo"), 1)]
+        );
+        let ranking = analysis::pagerank(&result);
+        assert_eq!(
+            Vec::from_iter(ranking.iter().map(|(url, _)| url.path())),
+            ["/bar", "/", "/foo"]
+        );
+        let total = ranking.iter().map(|(_, rank)| rank).sum::<f64>();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn near_duplicates() {
+        const PRODUCT: &str = r#"<html><head><title>Shop</title></head><body><nav><a href="/">home</a><a href="/cart">cart</a></nav><main><h1>Product</h1><p class="price">Free delivery on every order, returns within thirty days</p></main><footer>Copyright the shop, all rights reserved</footer></body></html>"#;
+        let server = Server::run()
+            .serve(
+                "/",
+                r#"<a href="/a">a</a><a href="/b">b</a><a href="/about">about</a>"#,
+            )
+            .serve("/a", PRODUCT)
+            .serve("/b", PRODUCT.replace("Shop", "Shop 2024-06-01").leak())
+            .serve(
+                "/about",
+                "We are a small team of people who sell things online, and have done since long before anyone else.",
+            );
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let result = build_graph(
+            &CrawlConfig {
+                max_depth: Some(1),
+                ..Default::default()
+            },
+            url("/"),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(
+            analysis::near_duplicates(&result, 3),
+            [vec![url("/a"), url("/b")]]
+        );
+        assert_eq!(
+            analysis::near_duplicates(&result, 0),
+            Vec::<Vec<Url>>::new()
+        );
+    }
+
+    #[tokio::test]
+    async fn path_tree() {
+        let server = Server::run()
+            .serve(
+                "/",
+                r#"<a href="/docs/a">a</a><a href="/docs/b?page=2">b</a>"#,
+            )
+            .serve("/docs/a", "aaaa")
+            .serve("/docs/b", "bb");
+        let root = server.url_str("/").parse::<Url>().unwrap();
+        let result = build_graph(&Default::default(), root.clone(), get_all_children).await;
+        let tree = analysis::path_tree(&result);
+        let host = &tree.children[0];
+        assert_eq!((tree.pages, tree.bytes, tree.children.len()), (3, 59, 1));
+        assert_eq!(
+            host.name,
+            format!("{}:{}", root.host_str().unwrap(), root.port().unwrap())
+        );
+        let docs = &host.children[0];
+        assert_eq!((docs.name.as_str(), docs.pages, docs.bytes), ("docs", 2, 6));
+        assert_eq!(
+            Vec::from_iter(docs.children.iter().map(|it| (it.name.as_str(), it.bytes))),
+            [("a", 4), ("b", 2)]
+        );
+        let json = serde_json::to_value(&docs.children[0]).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"name": "a", "pages": 1, "bytes": 4})
+        );
+    }
+
+    #[tokio::test]
+    async fn canonicalize_www() {
+        let server = Server::run().serve("/foo", "");
+        let port = server.addr().port();
+        let body = format!(
+            r#"<a href="http://www.example.test:{port}/foo">www</a><a href="http://example.test:{port}/foo">bare</a><a href="http://alias.test:{port}/foo">alias</a>"#
+        );
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/"))
+                .respond_with(status_code(200).body(body)),
+        );
+        let root = format!("http://www.example.test:{port}/")
+            .parse::<Url>()
+            .unwrap();
+        let result = build_graph(
+            &CrawlConfig {
+                canonicalize_www: true,
+                host_aliases: HashMap::from([("alias.test".into(), "example.test".into())]),
+                resolve: vec![("example.test".into(), server.addr())],
+                ..Default::default()
+            },
+            root.clone(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 2);
+        assert!(result.contains(&format!("http://example.test:{port}/foo").parse().unwrap()));
+        assert!(result.contains(&root));
+    }
+
+    #[tokio::test]
+    async fn journal() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::path("/"))
+                .respond_with(status_code(200).body(LINK_TO_FOO)),
+        );
+        server.expect(Expectation::matching(request::path("/foo")).respond_with(status_code(200)));
+        let path = std::env::temp_dir().join(format!("sprawl-journal-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let root: Url = server.url_str("/").parse().unwrap();
+        let config = CrawlConfig {
+            journal: Some(path.clone()),
+            ..Default::default()
+        };
+        build_graph(&config, root.clone(), {
+            let cancellation = config.cancellation.clone();
+            move |response, depth| {
+                cancellation.cancel();
+                get_all_children(response, depth)
+            }
+        })
+        .await;
+        // only /foo is fetched this time
+        let result = build_graph(
+            &CrawlConfig {
+                journal: Some(path.clone()),
+                ..Default::default()
+            },
+            root,
+            get_all_children,
+        )
+        .await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(result.graph.node_count(), 2);
+        assert_eq!(result.graph.edge_count(), 1);
+        assert_eq!(
+            result.pages[&server.url_str("/foo").parse::<Url>().unwrap()].depth,
+            1
+        );
+    }
+
+    #[test]
+    fn journaled_status() {
+        let error = FetchError::Status(StatusCode::NOT_FOUND);
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(json, r#"{"Status":404}"#);
+        assert_eq!(serde_json::from_str::<FetchError>(&json).unwrap(), error);
+    }
+
+    #[tokio::test]
+    async fn unopenable_journal() {
+        let server = Server::run().serve("/", "");
+        // a directory can't be read as a journal
+        let config = CrawlConfig {
+            journal: Some(std::env::temp_dir()),
+            ..Default::default()
+        };
+        let url = server.url_str("/").parse().unwrap();
+        let result = build_graph(&config, url, get_all_children).await;
+        assert!(result.pages.values().all(|page| page.result.is_ok()));
+    }
+
+    #[tokio::test]
+    async fn checkpoint() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::path("/"))
+                .respond_with(status_code(200).body(r#"<a href="/slow">slow</a>"#)),
+        );
+        server.expect(
+            Expectation::matching(request::path("/slow"))
+                .respond_with(delay_and_then(Duration::from_millis(500), status_code(200))),
+        );
+        let path = std::env::temp_dir().join(format!("sprawl-checkpoint-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let pages = |path: &std::path::Path| {
+            let mut pages = Vec::from_iter(
+                std::fs::read_to_string(path)
+                    .unwrap()
+                    .lines()
+                    .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+                    .map(|record| record["urls"][0].as_str().unwrap().to_owned()),
+            );
+            pages.sort();
+            pages
+        };
+        let root: Url = server.url_str("/").parse().unwrap();
+        let config = CrawlConfig {
+            checkpoint: Some((CheckpointInterval::Pages(1), path.clone())),
+            ..Default::default()
+        };
+        let result = build_graph(&config, root.clone(), {
+            let path = path.clone();
+            let root = root.clone();
+            move |response, depth| {
+                if response.final_url.path() == "/slow" {
+                    // written while /slow was being fetched
+                    assert_eq!(pages(&path), [root.to_string()]);
+                }
+                get_all_children(response, depth)
+            }
+        })
+        .await;
+        assert_eq!(result.pages.len(), 2);
+        let mut temporary = path.clone().into_os_string();
+        temporary.push(".tmp");
+        assert!(!std::path::Path::new(&temporary).exists());
+        // resuming from the last one fetches nothing
+        let resumed = build_graph(
+            &CrawlConfig {
+                journal: Some(path.clone()),
+                ..Default::default()
+            },
+            root,
+            get_all_children,
+        )
+        .await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(resumed.pages.len(), 2);
+        assert_eq!(resumed.graph.edge_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn max_edges() {
+        let server = Server::run()
+            .serve(
+                "/",
+                Box::leak(format!("{}{}", LINK_TO_FOO, LINK_TO_BAR).into_boxed_str()),
+            )
+            .serve("/bar", LINK_TO_FOO);
+        let result = build_graph(
+            &CrawlConfig {
+                max_edges: Some(1),
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.graph.node_count(), 2);
+        assert_eq!(result.graph.edge_count(), 1);
+        assert!(result.contains(&server.url_str("/bar").parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn max_pages() {
+        // only the first two are served, so fetching the others would fail the test
+        let server = Server::run()
+            .serve(
+                "/",
+                r#"<a href="/a">a</a><a href="/b">b</a><a href="/c">c</a><a href="/d">d</a>"#,
+            )
+            .serve("/a", "");
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let result = build_graph(
+            &CrawlConfig {
+                max_pages: Some(2),
+                strategy: Strategy::BreadthFirst,
+                max_concurrency: 1,
+                ..Default::default()
+            },
+            url("/"),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.graph.node_count(), 2);
+        assert_eq!(result.graph.edge_count(), 1);
+        assert!(result.pages[&url("/a")].result.is_ok());
+        assert_eq!(
+            result.frontier,
+            HashSet::from_iter(["/b", "/c", "/d"].map(url))
+        );
+    }
+
+    #[tokio::test]
+    async fn case_insensitive_paths() {
+        let server = Server::run()
+            .serve("/", r#"<a href="/Foo">Foo</a><a href="/foo">foo</a>"#)
+            .serve("/foo", "");
+        let result = build_graph(
+            &CrawlConfig {
+                case_insensitive_paths: true,
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 2);
+        assert_eq!(result.graph.edge_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn index_files() {
+        const LINKS: &str = r#"<a href="/dir/">dir</a><a href="/dir/index.html">index</a>"#;
+        let server = Server::run().serve("/", LINKS).serve("/dir/", "");
+        let result = build_graph(
+            &CrawlConfig {
+                index_files: vec!["index.html".into(), "default.aspx".into()],
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 2);
+        assert_eq!(result.graph.edge_count(), 1);
+
+        // off by default
+        let server = Server::run()
+            .serve("/", LINKS)
+            .serve("/dir/", "")
+            .serve("/dir/index.html", "");
+        let result = build_graph(
+            &Default::default(),
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn custom_normalize() {
+        let server = Server::run()
+            .serve(
+                "/",
+                r#"<a href="/foo?b=2&a=1">foo</a><a href="/foo?a=1&sid=x&b=2">foo</a>"#,
+            )
+            .serve("/foo", "");
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let result = build_graph(
+            &CrawlConfig {
+                normalize: Some(Arc::new(|mut url| {
+                    if url.query().is_none() {
+                        return url;
+                    }
+                    let mut pairs = Vec::from_iter(
+                        url.query_pairs()
+                            .filter(|(key, _)| key != "sid")
+                            .map(|(key, value)| (key.into_owned(), value.into_owned())),
+                    );
+                    pairs.sort();
+                    url.query_pairs_mut().clear().extend_pairs(pairs);
+                    url
+                })),
+                ..Default::default()
+            },
+            url("/"),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 2);
+        assert_eq!(
+            result.pages[&url("/foo?a=1&b=2")].discovered_as,
+            [url("/foo?a=1&sid=x&b=2"), url("/foo?b=2&a=1")]
+        );
+    }
+
+    #[tokio::test]
+    async fn should_retry() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::path("/"))
+                .times(3)
+                .respond_with(cycle(vec![
+                    Box::new(status_code(503)),
+                    Box::new(status_code(418)),
+                    Box::new(status_code(200).body(LINK_TO_FOO)),
+                ])),
+        );
+        server.expect(
+            Expectation::matching(request::path("/foo"))
+                .times(3)
+                .respond_with(status_code(418)),
+        );
+        let attempts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let result = build_graph(
+            &CrawlConfig {
+                max_retries: 2,
+                should_retry: Some(Arc::new({
+                    let attempts = attempts.clone();
+                    move |attempt| {
+                        attempts.lock().unwrap().push(attempt.attempt);
+                        attempt.status.as_u16() >= 418
+                    }
+                })),
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 2);
+        let mut attempts = attempts.lock().unwrap().clone();
+        attempts.sort();
+        assert_eq!(attempts, [1, 1, 2, 2]);
+        assert_eq!(result.stats.requests, 6);
+    }
+
+    #[tokio::test]
+    async fn retry_backoff() {
+        let server = Server::run().serve("/", LINK_TO_FOO);
+        server.expect(
+            Expectation::matching(request::path("/foo"))
+                .times(3)
+                .respond_with(cycle(vec![
+                    Box::new(status_code(503)),
+                    Box::new(status_code(503)),
+                    Box::new(status_code(200).body("foo")),
+                ])),
+        );
+        let config = CrawlConfig {
+            max_retries: 2,
+            retry_base_delay: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let result = build_graph(
+            &config,
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        // 50ms, then 100ms
+        assert!(start.elapsed() >= Duration::from_millis(150));
+        assert_eq!(
+            result.pages[&server.url_str("/foo").parse().unwrap()].body(),
+            Ok("foo")
+        );
+
+        // nothing's listening once the listener is dropped
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let result = build_graph(
+            &CrawlConfig {
+                retry_base_delay: Duration::ZERO,
+                ..config
+            },
+            format!("http://{addr}/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert!(matches!(
+            result.pages.values().next().unwrap().result,
+            Err(FetchError::Connect(_))
+        ));
+        assert_eq!(result.stats.requests, 3);
+    }
+
+    #[tokio::test]
+    async fn save_bodies_to() {
+        let server = Server::run()
+            .serve("/", r#"<a href="/a">a</a>"#)
+            .serve("/a", r#"<a href="/a/b?c=d">b</a>"#)
+            .serve("/a/b", "b");
+        let dir = std::env::temp_dir().join(format!("sprawl-mirror-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        build_graph(
+            &CrawlConfig {
+                save_bodies_to: Some(dir.clone()),
+                strategy: Strategy::BreadthFirst,
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        // one directory for the server's host and port
+        let host = std::fs::read_dir(&dir)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let read = |path: &str| std::fs::read_to_string(host.join(path)).unwrap();
+        assert_eq!(read("index.html"), r#"<a href="/a">a</a>"#);
+        assert_eq!(read("a/index.html"), r#"<a href="/a/b?c=d">b</a>"#);
+        assert_eq!(read("a/b_c=d"), "b");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn mixed_content() {
+        let root = "https://example.test/".parse::<Url>().unwrap();
+        let result = build_graph_from_seed_body(
+            &Default::default(),
+            root.clone(),
+            r#"<a href="http://127.0.0.1:1/img">img</a><a href="https://127.0.0.1:1/ok">ok</a>"#
+                .into(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(
+            analysis::mixed_content(&result),
+            [(root, "http://127.0.0.1:1/img".parse().unwrap())]
+        );
+    }
+
+    #[tokio::test]
+    async fn pause() {
+        let server = Server::run().serve("/", "");
+        let fetched = Arc::new(AtomicBool::new(false));
+        let config = CrawlConfig {
+            request_hook: Some(Arc::new({
+                let fetched = fetched.clone();
+                move |_, request| {
+                    fetched.store(true, Ordering::SeqCst);
+                    request
+                }
+            })),
+            ..Default::default()
+        };
+        config.pause.pause();
+        let (result, ()) = tokio::join!(
+            build_graph(
+                &config,
+                server.url_str("/").parse().unwrap(),
+                get_all_children
+            ),
+            async {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                assert!(!fetched.load(Ordering::SeqCst));
+                config.pause.resume();
+            }
+        );
+        assert!(fetched.load(Ordering::SeqCst));
+        assert!(!result.root_failed());
+    }
+
+    #[tokio::test]
+    async fn extract_from_scripts() {
+        let server = Server::run()
+            .serve(
+                "/",
+                r#"<script src="/app.js"></script><script>go("/inline", 'text/html')</script>"#,
+            )
+            .serve("/inline", "")
+            .serve("/api/items", "");
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/app.js")).respond_with(
+                status_code(200)
+                    .insert_header("Content-Type", "text/javascript")
+                    .body(r#"const routes = ["/api/items", "//cdn.example/x", "not/a/path"];"#),
+            ),
+        );
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let result = build_graph(
+            &CrawlConfig {
+                extract_from_scripts: true,
+                ..Default::default()
+            },
+            url("/"),
+            get_all_children,
+        )
+        .await;
+        let mut pages = Vec::from_iter(result.pages.keys().cloned());
+        pages.sort();
+        assert_eq!(
+            pages,
+            [url("/"), url("/api/items"), url("/app.js"), url("/inline")]
+        );
+    }
+
+    #[tokio::test]
+    async fn success_statuses() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::path("/"))
+                .respond_with(status_code(401).body(LINK_TO_FOO)),
+        );
+        let server = server.no_serve("/foo");
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let result = build_graph(
+            &CrawlConfig {
+                success_statuses: HashSet::from_iter((200..300).chain([401])),
+                ..Default::default()
+            },
+            url("/"),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages[&url("/")].body(), Ok(LINK_TO_FOO));
+        assert_eq!(
+            result.pages[&url("/foo")].result,
+            Err(FetchError::Status(StatusCode::BAD_REQUEST))
+        );
+    }
+
+    #[tokio::test]
+    async fn get_children_sees_status() {
+        let server = Server::run().serve("/", r#"<a href="/missing">missing</a>"#);
+        server.expect(
+            Expectation::matching(request::path("/missing"))
+                .respond_with(status_code(404).body(LINK_TO_FOO)),
+        );
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let result = build_graph(
+            &CrawlConfig {
+                success_statuses: HashSet::from_iter((200..300).chain([404])),
+                ..Default::default()
+            },
+            url("/"),
+            {
+                let seen = seen.clone();
+                move |response: &PageResponse, depth| {
+                    seen.borrow_mut()
+                        .push((response.final_url.path().to_owned(), response.status));
+                    // a soft 404's links aren't worth following
+                    match response.status {
+                        Some(StatusCode::OK) => get_all_children(response, depth),
+                        _ => None,
+                    }
+                }
+            },
+        )
+        .await;
+        assert_eq!(
+            seen.take(),
+            [
+                (String::from("/"), Some(StatusCode::OK)),
+                (String::from("/missing"), Some(StatusCode::NOT_FOUND)),
+            ]
+        );
+        assert_eq!(result.pages.len(), 2);
+    }
+
+    #[test]
+    fn extractor() {
+        let url = "https://example.test/dir/page".parse::<Url>().unwrap();
+        let body = r##"
+            <base href="https://example.test/base/">
+            <a href="a#top">a</a>
+            <a href="b" rel="external NoFollow">b</a>
+            <a href="http://example.test/c">c</a>
+            <iframe src="frame"></iframe>
+        "##;
+        let links = extract::Extractor::new()
+            .follow_nofollow(false)
+            .include_iframes(true)
+            .strip_fragments(true)
+            .respect_base_href(true)
+            .allowed_schemes(["https"])
+            .links(&url, body);
+        assert_eq!(
+            links,
+            [
+                "https://example.test/base/a".parse::<Url>().unwrap(),
+                "https://example.test/base/frame".parse().unwrap(),
+            ]
+        );
+        assert_eq!(
+            extract::Extractor::new().links(&url, body),
+            extract::links(&url, body)
+        );
+    }
+
+    #[test]
+    fn profiles() {
+        let polite = CrawlConfig::with_profile(Profile::Polite);
+        assert_eq!(polite.strategy, Strategy::BreadthFirst);
+        assert_eq!(polite.per_host_concurrency, Some(1));
+        assert_eq!(polite.per_host_delay, Some(Duration::from_secs(1)));
+        let default = CrawlConfig::with_profile(Profile::Default);
+        assert_eq!(default.strategy, CrawlConfig::default().strategy);
+        assert_eq!(default.max_retries, CrawlConfig::default().max_retries);
+    }
+
+    #[test]
+    fn describe() {
+        let plan = CrawlConfig {
+            max_depth: Some(3),
+            basic_auth: HashMap::from([("example.test".into(), ("me".into(), "secret".into()))]),
+            should_keep: Some(Arc::new(|_, _| true)),
+            ..CrawlConfig::with_profile(Profile::Polite)
+        }
+        .describe();
+        assert_eq!(plan["scope"]["max_depth"], 3);
+        assert_eq!(plan["politeness"]["strategy"], "BreadthFirst");
+        assert_eq!(plan["politeness"]["per_host_concurrency"], 1);
+        assert_eq!(plan["politeness"]["per_host_delay"], 1.0);
+        assert_eq!(plan["hooks"], serde_json::json!(["should_keep"]));
+        assert_eq!(
+            plan["fetching"]["basic_auth_hosts"],
+            serde_json::json!(["example.test"])
+        );
+        assert!(!plan.to_string().contains("secret"));
+    }
+
+    #[test]
+    fn max_links_extracted() {
+        let url = "https://example.test/".parse::<Url>().unwrap();
+        let body = r#"<a href="a">a</a><a href="b">b</a><iframe src="frame"></iframe>"#;
+        let extractor = extract::Extractor::new().include_iframes(true);
+        assert_eq!(extractor.links(&url, body).len(), 3);
+        assert_eq!(
+            extractor.max_links_extracted(1).links(&url, body),
+            ["https://example.test/a".parse::<Url>().unwrap()]
+        );
+        // links that are dropped don't use up the cap
+        let body = r#"<a href="http://[::1">bad</a><a href="mailto:me@example.test">me</a><a href="a">a</a>"#;
+        assert_eq!(
+            extract::Extractor::new()
+                .allowed_schemes(["https"])
+                .max_links_extracted(1)
+                .links(&url, body),
+            [url.join("a").unwrap()]
+        );
+        // stopping partway through a srcset
+        let body = r#"<a href="a">a</a><img srcset="x.png 1x, y.png 2x, z.png 3x">"#;
+        let extractor = extract::Extractor::new()
+            .include_srcset(true)
+            .max_links_extracted(2);
+        assert_eq!(
+            extractor.links(&url, body),
+            ["a", "x.png"].map(|it| url.join(it).unwrap())
+        );
+    }
+
+    #[test]
+    fn dot_by_host() {
+        let mut graph = DiGraph::new();
+        let root = graph.add_node(Url::parse("http://example.com/").unwrap());
+        let other = graph.add_node(Url::parse("http://other.test/").unwrap());
+        let foo = graph.add_node(Url::parse("http://example.com/foo").unwrap());
+        let kind = |kind| Edge {
+            kind,
+            count: 1,
+            attributes: Vec::new(),
+        };
+        graph.add_edge(root, other, kind(EdgeKind::Link));
+        graph.add_edge(root, foo, kind(EdgeKind::Redirect));
+        assert_eq!(
+            export::to_dot_by_host(&graph),
+            r#"digraph {
+    subgraph "cluster_example.com" {
+        label = "example.com"
+        0 [ label = "http://example.com/" ]
+        2 [ label = "http://example.com/foo" ]
+    }
+    subgraph "cluster_other.test" {
+        label = "other.test"
+        1 [ label = "http://other.test/" ]
+    }
+    0 -> 1
+    0 -> 2 [ style = dashed ]
+}
+"#
+        );
+    }
+
+    #[tokio::test]
+    async fn long_chain() {
+        const LENGTH: usize = 5000;
+        let url = |n: usize| format!("http://example.test/{n}").parse::<Url>().unwrap();
+        // cached, so that nothing is fetched
+        let pages = HashMap::from_iter((0..LENGTH).map(|n| {
+            let page = NodeValue {
+                result: Ok(PageResponse::new(
+                    url(n),
+                    format!(r#"<a href="/{}">next</a>"#, n + 1),
+                )),
+                error_response: None,
+                last_modified: None,
+                depth: n,
+                pagination: Vec::new(),
+                fetched_at: std::time::SystemTime::now(),
+                discovered_as: Vec::new(),
+                metadata: None,
+            };
+            (url(n), page)
+        }));
+        let result = build_graph(
+            &CrawlConfig {
+                cache: Arc::new(pages),
+                cache_ttl: Duration::from_secs(60 * 60),
+                max_depth: Some(LENGTH - 1),
+                ..Default::default()
+            },
+            url(0),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), LENGTH);
+    }
+
+    #[tokio::test]
+    async fn same_child_after_normalization() {
+        let server = Server::run()
+            .serve(
+                "/",
+                r#"<a href="/foo#top">foo</a><a href="/FOO">foo</a><a href="/foo">foo</a>"#,
+            )
+            .serve("/foo", "");
+        let result = build_graph(
+            &CrawlConfig {
+                case_insensitive_paths: true,
+                count_links: true,
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 2);
+        assert_eq!(result.graph.edge_count(), 1);
+        assert_eq!(result.graph.edge_weights().next().unwrap().count, 3);
+    }
+
+    #[tokio::test]
+    async fn revisited_while_expanding() {
+        const LENGTH: usize = 500;
+        let url = |n: usize| format!("http://example.test/{n}").parse::<Url>().unwrap();
+        // links back to n / 2 reach pages from nearer the seed while they're
+        // still being expanded from further away
+        let pages = HashMap::from_iter((0..LENGTH).map(|n| {
+            let links = String::from_iter(
+                [n + 1, n + 2, n + 3, n / 2]
+                    .into_iter()
+                    .filter(|it| *it < LENGTH)
+                    .map(|it| format!(r#"<a href="/{it}">link</a>"#)),
+            );
+            let page = NodeValue {
+                result: Ok(PageResponse::new(url(n), links)),
+                error_response: None,
+                last_modified: None,
+                depth: 0,
+                pagination: Vec::new(),
+                fetched_at: std::time::SystemTime::now(),
+                discovered_as: Vec::new(),
+                metadata: None,
+            };
+            (url(n), page)
+        }));
+        for strategy in [Strategy::Concurrent, Strategy::BreadthFirst] {
+            let result = build_graph(
+                &CrawlConfig {
+                    cache: Arc::new(pages.clone()),
+                    cache_ttl: Duration::from_secs(60 * 60),
+                    strategy,
+                    ..Default::default()
+                },
+                url(0),
+                get_all_children,
+            )
+            .await;
+            assert_eq!(result.pages.len(), LENGTH);
+        }
+    }
+
+    #[tokio::test]
+    async fn expanded_twice() {
+        // /p is reached from /a first, then again from nearer the seed, so its
+        // children are found twice
+        let server = Server::run()
+            .serve("/", r#"<a href="/a">a</a><a href="/p">p</a>"#)
+            .serve("/a", r#"<a href="/p">p</a>"#)
+            .serve(
+                "/p",
+                r#"<a href="/c">c</a><link rel="canonical" href="/p">"#,
+            )
+            .serve("/c", "");
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let result = build_graph(
+            &CrawlConfig {
+                strategy: Strategy::DepthFirst,
+                max_concurrency: 1,
+                count_links: true,
+                link_attributes: true,
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            {
+                let order = order.clone();
+                move |response, depth| {
+                    order
+                        .borrow_mut()
+                        .push(response.final_url.path().to_owned());
+                    get_all_children(response, depth)
+                }
+            },
+        )
+        .await;
+        assert_eq!(order.borrow().iter().filter(|it| *it == "/p").count(), 2);
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let (p, c) = (
+            result.node_by_url(&url("/p")).unwrap(),
+            result.node_by_url(&url("/c")).unwrap(),
+        );
+        let edges = Vec::from_iter(result.graph.edges_connecting(p, c));
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].weight().count, 1);
+        assert_eq!(edges[0].weight().attributes.len(), 1);
+        assert_eq!(result.pages[&url("/p")].depth, 1);
+    }
+
+    #[tokio::test]
+    async fn frontier_at_depth() {
+        let server = Server::run()
+            .serve("/", r#"<a href="/foo">foo</a><a href="/bar">bar</a>"#)
+            .serve("/foo", r#"<a href="/bar">bar</a><a href="/baz">baz</a>"#)
+            .serve("/bar", r#"<a href="/qux">qux</a>"#);
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        assert_eq!(
+            crate::frontier_at_depth(&Default::default(), url("/"), 2, get_all_children).await,
+            HashSet::from([url("/baz"), url("/qux")])
+        );
+    }
+
+    #[tokio::test]
+    async fn crawl_one() {
+        // only the root is served, so fetching its children would fail the test
+        let server = Server::run()
+            .redirect("/", "/home")
+            .serve("/home", r#"<a href="foo#top">foo</a>"#)
+            .no_serve("/missing");
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let (response, children) =
+            crate::crawl_one(&Default::default(), url("/"), get_all_children).await;
+        let response = response.unwrap();
+        assert_eq!(response.final_url, url("/home"));
+        assert_eq!(response.body, r#"<a href="foo#top">foo</a>"#);
+        assert_eq!(children, HashSet::from([url("/foo")]));
+        let (response, children) =
+            crate::crawl_one(&Default::default(), url("/missing"), get_all_children).await;
+        assert!(response.is_err());
+        assert!(children.is_empty());
+    }
+
+    #[tokio::test]
+    async fn estimate_size() {
+        for (max_depth, pages) in [(Some(4), Some(1 + 2 + 4 + 8 + 16)), (None, None)] {
+            // each page links to two more
+            let server = Server::run()
+                .serve("/", r#"<a href="/a">a</a><a href="/b">b</a>"#)
+                .serve("/a", r#"<a href="/a1">a1</a><a href="/a2">a2</a>"#)
+                .serve("/b", r#"<a href="/b1">b1</a><a href="/b2">b2</a>"#);
+            let estimate = crate::estimate_size(
+                &CrawlConfig {
+                    max_depth,
+                    ..Default::default()
+                },
+                server.url_str("/").parse().unwrap(),
+                2,
+                get_all_children,
+            )
+            .await;
+            assert_eq!(estimate.levels, [1, 2, 4]);
+            assert_eq!(estimate.branching_factor, 2.0);
+            assert_eq!(estimate.pages, pages);
+        }
+    }
+
+    #[tokio::test]
+    async fn login() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("POST", "/login"))
+                .respond_with(status_code(200)),
+        );
+        let server = server.serve("/", "");
+        let login = server.url_str("/login").parse::<Url>().unwrap();
+        let root = server.url_str("/").parse::<Url>().unwrap();
+        let result = build_graph(
+            &CrawlConfig {
+                login: Some(Arc::new(move |client: reqwest::Client| {
+                    let login = login.clone();
+                    Box::pin(async move {
+                        client.post(login).send().await.map_err(|e| e.to_string())?;
+                        Ok(())
+                    })
+                })),
+                ..Default::default()
+            },
+            root.clone(),
+            get_all_children,
+        )
+        .await;
+        assert!(!result.root_failed());
+        let result = build_graph(
+            &CrawlConfig {
+                login: Some(Arc::new(|_| Box::pin(async { Err("nope".into()) }))),
+                ..Default::default()
+            },
+            root.clone(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(
+            result.pages[&root].result,
+            Err(FetchError::Login("nope".into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn error_categories() {
+        let server = Server::run()
+            .serve(
+                "/",
+                r#"<a href="/foo">foo</a><a href="/bar">bar</a><a href="/baz">baz</a>"#,
+            )
+            .no_serve("/foo")
+            .no_serve("/bar");
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/baz"))
+                .respond_with(status_code(503)),
+        );
+        let result = build_graph(
+            &Default::default(),
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.stats.failed, 3);
+        assert_eq!(
+            result.stats.errors,
+            BTreeMap::from([("status 400".into(), 2), ("status 503".into(), 1)])
+        );
+    }
+
+    #[tokio::test]
+    async fn basic_auth() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/"),
+                // base64 of user:pass
+                request::headers(contains(("authorization", "Basic dXNlcjpwYXNz"))),
+            ])
+            .respond_with(status_code(200)),
+        );
+        let root = server.url_str("/").parse::<Url>().unwrap();
+        let result = build_graph(
+            &CrawlConfig {
+                basic_auth: HashMap::from([(
+                    root.host_str().unwrap().into(),
+                    ("user".into(), "pass".into()),
+                )]),
+                ..Default::default()
+            },
+            root,
+            get_all_children,
+        )
+        .await;
+        assert!(!result.root_failed());
+    }
+
+    #[tokio::test]
+    async fn max_body_bytes() {
+        let server = Server::run()
+            .serve("/", r#"<a href="/big">big</a><a href="/small">small</a>"#)
+            .serve("/big", Box::leak("0".repeat(100).into_boxed_str()))
+            .serve("/small", "0");
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let result = build_graph(
+            &CrawlConfig {
+                max_body_bytes: Some(64),
+                ..Default::default()
+            },
+            url("/"),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(
+            result.pages[&url("/big")].result,
+            Err(FetchError::Skipped(SkipReason::BodyTooLarge {
+                size: Some(100),
+                limit: 64
+            }))
+        );
+        assert!(result.pages[&url("/small")].result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn generic() {
+        // each number links to its double and its successor, mod 10
+        let neighbors = |n: u32| async move { vec![n * 2 % 10, (n + 1) % 10] };
+        let mut config = CrawlConfig {
+            max_concurrency: 4,
+            ..Default::default()
+        };
+        let graph = build_graph_generic(&config, [1, 1], neighbors).await;
+        assert_eq!(graph.node_count(), 10);
+        // 1 doubles to its successor
+        assert_eq!(graph.edge_count(), 19);
+        assert!(graph.node_weights().all(|n| *n < 10));
+        // the crawl's limits apply too
+        config.max_depth = Some(1);
+        let graph = build_graph_generic(&config, [1], neighbors).await;
+        assert_eq!(Vec::from_iter(graph.node_weights()), [&1, &2]);
+        assert_eq!(graph.edge_count(), 1);
+        config.max_depth = None;
+        config.cancellation.cancel();
+        let graph = build_graph_generic(&config, [1], neighbors).await;
+        assert_eq!(graph.node_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn discovered_as() {
+        let server = Server::run()
+            .serve(
+                "/",
+                r##"<a href="/foo#b">b</a><a href="/foo">foo</a><a href="/foo#a">a</a>"##,
+            )
+            .serve("/foo", "");
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let result = build_graph(&Default::default(), url("/"), get_all_children).await;
+        let foo = url("/foo");
+        assert_eq!(
+            result.pages[&foo].discovered_as,
+            [foo.join("#a").unwrap(), foo.join("#b").unwrap()]
+        );
+        assert_eq!(result.pages[&url("/")].discovered_as, []);
+    }
+
+    #[tokio::test]
+    async fn idle_timeout() {
+        let server = Server::run().serve("/", LINK_TO_FOO);
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/foo"))
+                .respond_with(delay_and_then(Duration::from_secs(10), status_code(200))),
+        );
+        let config = CrawlConfig {
+            idle_timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+        let started = Instant::now();
+        let result = build_graph(
+            &config,
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(!config.cancellation.is_cancelled());
+        let foo = server.url_str("/foo").parse().unwrap();
+        assert_eq!(result.pages[&foo].result, Err(FetchError::Cancelled));
+    }
+
+    #[tokio::test]
+    async fn max_duration() {
+        let server = Server::run().serve("/", LINK_TO_FOO);
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/foo"))
+                .respond_with(delay_and_then(Duration::from_secs(10), status_code(200))),
+        );
+        let config = CrawlConfig {
+            max_duration: Some(Duration::from_millis(500)),
+            ..Default::default()
+        };
+        let started = Instant::now();
+        let result = build_graph(
+            &config,
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        assert!(started.elapsed() >= Duration::from_millis(500));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(!config.cancellation.is_cancelled());
+        assert!(result.pages[&server.url_str("/").parse().unwrap()]
+            .result
+            .is_ok());
+        let foo = server.url_str("/foo").parse().unwrap();
+        assert_eq!(result.pages[&foo].result, Err(FetchError::Cancelled));
+    }
+
+    #[test]
+    fn protocol_relative() {
+        let url = "https://example.test/a/b".parse::<Url>().unwrap();
+        let body = r#"<a href="//other.test/x">x</a><a href=" //other.test:8443/y?z">y</a>"#;
+        let expected = [
+            "https://other.test/x".parse::<Url>().unwrap(),
+            "https://other.test:8443/y?z".parse().unwrap(),
+        ];
+        assert_eq!(extract::links(&url, body), expected);
+        assert_eq!(extract::Extractor::new().links(&url, body), expected);
+    }
+
+    #[test]
+    fn malformed_hrefs() {
+        let url = "https://example.test/".parse::<Url>().unwrap();
+        let body = r#"<a href="ok">ok</a><a href="http://[::1">bad</a><a href="https://a b.test/">space</a>"#;
+        assert_eq!(
+            extract::links(&url, body),
+            ["https://example.test/ok".parse::<Url>().unwrap()]
+        );
+        assert_eq!(
+            extract::malformed_hrefs(&url, body),
+            ["http://[::1", "https://a b.test/"]
+        );
+    }
+
+    #[test]
+    fn srcset() {
+        assert_eq!(
+            extract::srcset(" small.jpg 640w,  large.jpg 2x ,a,b.jpg 1x,c,d.png"),
+            ["small.jpg", "large.jpg", "a,b.jpg", "c,d.png"]
+        );
+        assert_eq!(extract::srcset("one.jpg, two.jpg,"), ["one.jpg", "two.jpg"]);
+        assert_eq!(
+            extract::srcset("x.jpg (odd, descriptor) 1x, y.jpg"),
+            ["x.jpg", "y.jpg"]
+        );
+        assert_eq!(extract::srcset(""), Vec::<&str>::new());
+        let url = "https://example.test/".parse::<Url>().unwrap();
+        let body =
+            r#"<img src="a.jpg" srcset="a2.jpg 2x"><picture><source srcset="b.webp"></picture>"#;
+        assert_eq!(
+            extract::Extractor::new()
+                .include_srcset(true)
+                .links(&url, body),
+            [url.join("a2.jpg").unwrap(), url.join("b.webp").unwrap()]
+        );
+        assert_eq!(extract::Extractor::new().links(&url, body), []);
+    }
+
+    #[test]
+    fn forms() {
+        let url = "https://example.test/shop/?old=1".parse::<Url>().unwrap();
+        let body = r#"
+            <form action="/search#results">
+                <input name="q" value="red shoes">
+                <input type="hidden" name="page" value="1">
+                <input type="checkbox" name="sale" checked>
+                <input type="checkbox" name="new" value="yes">
+                <input type="radio" name="sort" value="price">
+                <input type="radio" name="sort" value="name" checked>
+                <select name="size"><option>S</option><option value="m" selected>M</option></select>
+                <select name="colour"><option>any</option></select>
+                <textarea name="note">hi</textarea>
+                <input type="submit" name="go" value="Go">
+                <input value="no name">
+            </form>
+            <form method="POST" action="/login"><input name="user"></form>
+            <form method="get"><button name="b">b</button></form>
+        "#;
+        let search = "https://example.test/search?q=red+shoes&page=1&sale=on&sort=name&size=m&colour=any&note=hi";
+        assert_eq!(
+            extract::forms(&url, body),
+            [
+                search.parse::<Url>().unwrap(),
+                "https://example.test/shop/".parse().unwrap()
+            ]
+        );
+        let links = |include| {
+            extract::Extractor::new()
+                .include_forms(include)
+                .links(&url, r#"<a href="/a">a</a><form action="/b"></form>"#)
+        };
+        assert_eq!(links(false), [url.join("/a").unwrap()]);
+        assert_eq!(
+            links(true),
+            [url.join("/a").unwrap(), url.join("/b").unwrap()]
+        );
+    }
+
+    #[tokio::test]
+    async fn count_links() {
+        let counts = |count_links| async move {
+            let server = Server::run()
+                .serve(
+                    "/",
+                    Box::leak(format!("{LINK_TO_FOO}{LINK_TO_FOO}{LINK_TO_BAR}").into_boxed_str()),
+                )
+                .serve("/foo", "")
+                .serve("/bar", "");
+            let result = build_graph(
+                &CrawlConfig {
+                    count_links,
+                    ..Default::default()
+                },
+                server.url_str("/").parse().unwrap(),
+                get_all_children,
+            )
+            .await;
+            let mut counts = Vec::from_iter(result.graph.edge_references().map(|edge| {
+                let path = result.graph[edge.target()].path().to_owned();
+                (path, edge.weight().count)
+            }));
+            counts.sort();
+            counts
+        };
+        assert_eq!(
+            counts(false).await,
+            [("/bar".into(), 1), ("/foo".into(), 1)]
+        );
+        assert_eq!(counts(true).await, [("/bar".into(), 1), ("/foo".into(), 2)]);
+    }
+
+    #[tokio::test]
+    async fn anchor_text() {
+        let server = Server::run()
+            .serve(
+                "/",
+                r#"<a href="/foo">Read
+                <em>more</em></a><a href="/foo">foo</a>"#,
+            )
+            .serve("/foo", "");
+        let result = build_graph(
+            &CrawlConfig {
+                count_links: true,
+                link_attributes: true,
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        let edge = result.graph.edge_weights().next().unwrap();
+        assert_eq!(edge.count, 2);
+        assert_eq!(
+            Vec::from_iter(edge.attributes.iter().map(|it| it.text.as_str())),
+            ["Read more", "foo"]
+        );
+    }
+
+    #[tokio::test]
+    async fn link_attributes() {
+        let server = Server::run()
+            .serve(
+                "/",
+                r#"<nav><ul><li><a href="/foo">foo</a></li></ul></nav>
+                <main><a href="/foo" rel="Sponsored nofollow" target="_blank">ad</a></main>
+                <a href="/bar">bar</a>"#,
+            )
+            .serve("/foo", "")
+            .serve("/bar", "");
+        let result = build_graph(
+            &CrawlConfig {
+                link_attributes: true,
+                ..Default::default()
+            },
+            server.url_str("/").parse().unwrap(),
+            get_all_children,
+        )
+        .await;
+        let mut edges = Vec::from_iter(result.graph.edge_references().map(|edge| {
+            let path = result.graph[edge.target()].path().to_owned();
+            let attributes = Vec::from_iter(
+                edge.weight()
+                    .attributes
+                    .iter()
+                    .map(|it| (it.rel.join(" "), it.target.clone(), it.section.clone())),
+            );
+            (path, attributes)
+        }));
+        edges.sort();
+        assert_eq!(
+            edges,
+            [
+                ("/bar".into(), vec![("".into(), None, None)]),
+                (
+                    "/foo".into(),
+                    vec![
+                        ("".into(), None, Some("nav".into())),
+                        (
+                            "sponsored nofollow".into(),
+                            Some("_blank".into()),
+                            Some("main".into())
+                        ),
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn known() {
+        // /old isn't served, so fetching it would fail the test
+        let server = Server::run()
+            .serve("/", r#"<a href="/old">old</a><a href="/new">new</a>"#)
+            .serve("/new", LINK_TO_FOO)
+            .serve("/foo", "");
+        let url = |path| server.url_str(path).parse::<Url>().unwrap();
+        let result = build_graph(
+            &CrawlConfig {
+                known: Arc::new(HashSet::from([url("/"), url("/old")])),
+                ..Default::default()
+            },
+            url("/"),
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 4);
+        assert_eq!(
+            result.pages[&url("/old")].result,
+            Err(FetchError::Skipped(SkipReason::Known))
+        );
+        assert!(result.pages[&url("/foo")].result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn same_origin() {
+        let other = Server::run();
+        let server = Server::run();
+        let url = |s: &str| s.parse::<Url>().unwrap();
+        let root = url(&server.url_str("/"));
+        let port = root.port().unwrap();
+        let mut other_scheme = root.clone();
+        other_scheme.set_scheme("https").unwrap();
+        let mut other_host = root.clone();
+        other_host.set_host(Some("localhost")).unwrap();
+        let other_port = url(&other.url_str("/"));
+        assert_ne!(other_port.port(), Some(port));
+        let out_of_scope = [other_scheme, other_host, other_port];
+        let body = String::from_iter(
+            out_of_scope
+                .iter()
+                .chain([&root.join("/foo").unwrap()])
+                .map(|it| format!(r#"<a href="{it}">link</a>"#)),
+        );
+        let server = server
+            .serve("/", Box::leak(body.into_boxed_str()))
+            .serve("/foo", "");
+        let result = build_graph(
+            &CrawlConfig {
+                scope: Scope::SameOrigin,
+                ..Default::default()
+            },
+            root,
+            get_all_children,
+        )
+        .await;
+        assert_eq!(result.pages.len(), 5);
+        assert!(result.pages[&url(&server.url_str("/foo"))].result.is_ok());
+        for url in out_of_scope {
+            assert_eq!(
+                result.pages[&url].result,
+                Err(FetchError::Skipped(SkipReason::OutOfScope))
+            );
+        }
+    }
+
+    #[test]
+    fn link_header() {
+        assert_eq!(
+            extract::link_header(
+                r#"<https://example.test/?page=3>; rel="next", </?page=1>;rel=prev"#
+            ),
+            [
+                ("https://example.test/?page=3", "next"),
+                ("/?page=1", "prev")
+            ]
+        );
+        assert_eq!(
+            extract::link_header(r#"</a>; title="x, y"; rel="next last", </b>"#),
+            [("/a", "next"), ("/a", "last")]
+        );
+        assert_eq!(extract::link_header(""), []);
+    }
+
+    #[tokio::test]
+    async fn follow_link_headers() {
+        let pages = |follow_link_headers| async move {
+            let server = Server::run();
+            for (path, link) in [
+                ("/", r#"</2>; rel="next""#),
+                (
+                    "/2",
+                    r#"</>; rel="prev", </3>; rel="next", </other>; rel="alternate""#,
+                ),
+                ("/3", r#"</2>; rel="prev""#),
+            ] {
+                server.expect(
+                    Expectation::matching(request::method_path("GET", path))
+                        .times(..)
+                        .respond_with(status_code(200).append_header("Link", link)),
+                );
+            }
+            let result = build_graph(
+                &CrawlConfig {
+                    follow_link_headers,
+                    ..Default::default()
+                },
+                server.url_str("/").parse().unwrap(),
+                get_all_children,
+            )
+            .await;
+            let mut paths = Vec::from_iter(result.pages.keys().map(|it| it.path().to_owned()));
+            paths.sort();
+            assert_eq!(result.graph.edge_count(), paths.len() * 2 - 2);
+            paths
+        };
+        assert_eq!(pages(false).await, ["/"]);
+        assert_eq!(pages(true).await, ["/", "/2", "/3"]);
+    }
+
+    async fn do_test(server: Server) -> (DiGraph<Url, Edge>, HashMap<Url, NodeValue>) {
+        build_graph(
+            &Default::default(),
+            server
+                .url("/")
+                .to_string()
+                .parse()
+                .expect("URI isn't a URL"),
+            get_all_children,
+        )
+        .await
+        .into_parts()
+    }
+
+    fn get_all_children(response: &PageResponse, _depth: usize) -> Option<HashSet<Url>> {
+        let links = extract::links(&response.final_url, &response.body);
+        Some(links.into_iter().collect())
+    }
+
+    trait ServerExt {
+        fn serve(self, path: &'static str, body: &'static str) -> Self;
+        fn no_serve(self, path: &'static str) -> Self;
+        fn redirect(self, path: &'static str, location: &'static str) -> Self;
+    }
+
+    impl ServerExt for Server {
+        fn serve(self, path: &'static str, body: &'static str) -> Self {
+            self.expect(
+                Expectation::matching(request::method_path("GET", path))
+                    .respond_with(status_code(200).body(body)),
+            );
+            self
+        }
+
+        fn no_serve(self, path: &'static str) -> Self {
+            self.expect(
+                Expectation::matching(request::method_path("GET", path))
+                    .respond_with(status_code(400)),
+            );
+            self
+        }
+
+        fn redirect(self, path: &'static str, location: &'static str) -> Self {
+            self.expect(
+                Expectation::matching(request::method_path("GET", path))
+                    .respond_with(status_code(302).append_header("Location", location)),
             );
             self
         }
@@ -0,0 +1,78 @@
+use std::{fs, io, path::PathBuf, sync::Mutex};
+use tracing::warn;
+use url::Url;
+
+/// Saves bodies to a directory tree mirroring their URLs, like
+/// `example.com/foo/bar.html`.
+pub(crate) struct Mirror {
+    root: PathBuf,
+    /// Held while moving files aside for directories.
+    lock: Mutex<()>,
+}
+
+impl Mirror {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Failures are logged rather than stopping the crawl.
+    pub fn save(&self, url: &Url, body: &str) {
+        let _lock = self.lock.lock().unwrap();
+        if let Err(e) = self.try_save(url, body) {
+            warn!("Couldn't save {url}: {e}");
+        }
+    }
+
+    fn try_save(&self, url: &Url, body: &str) -> io::Result<()> {
+        let mut host = sanitize(url.host_str().unwrap_or("_"));
+        if let Some(port) = url.port() {
+            host = format!("{host}_{port}");
+        }
+        let mut segments = Vec::from_iter(url.path_segments().into_iter().flatten().map(sanitize));
+        let mut file = segments.pop().unwrap_or_default();
+        if let Some(query) = url.query() {
+            file = format!("{file}_{}", sanitize(query));
+        }
+        let mut path = self.root.join(host);
+        for segment in segments.iter().filter(|it| !it.is_empty()) {
+            path.push(segment);
+            if path.is_file() {
+                // `/foo` was saved before `/foo/bar`, so it becomes `foo/index.html`
+                let aside = path.with_extension("sprawl-tmp");
+                fs::rename(&path, &aside)?;
+                fs::create_dir(&path)?;
+                fs::rename(&aside, path.join("index.html"))?;
+            }
+        }
+        fs::create_dir_all(&path)?;
+        match file.is_empty() {
+            true => path.push("index.html"),
+            false => {
+                path.push(file);
+                if path.is_dir() {
+                    path.push("index.html");
+                }
+            }
+        }
+        fs::write(path, body)
+    }
+}
+
+/// Replaces anything that could escape the directory or isn't allowed in file
+/// names on common platforms.
+fn sanitize(segment: &str) -> String {
+    match segment {
+        "." | ".." => "_".repeat(segment.len()),
+        _ => segment
+            .chars()
+            .map(|c| match c {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+                c if c.is_control() => '_',
+                c => c,
+            })
+            .collect(),
+    }
+}
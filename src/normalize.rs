@@ -0,0 +1,72 @@
+use std::{collections::HashMap, fmt};
+
+use url::Url;
+
+use crate::{CrawlConfig, Normalize};
+
+/// How the crawl names pages, so that different spellings of the same page
+/// share a node.
+#[derive(Clone, Default)]
+pub(crate) struct Normalizer {
+    host_aliases: HashMap<String, String>,
+    canonicalize_www: bool,
+    case_insensitive_paths: bool,
+    index_files: Vec<String>,
+    custom: Option<Normalize>,
+}
+
+impl fmt::Debug for Normalizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Normalizer")
+            .field("host_aliases", &self.host_aliases)
+            .field("canonicalize_www", &self.canonicalize_www)
+            .field("case_insensitive_paths", &self.case_insensitive_paths)
+            .field("index_files", &self.index_files)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Normalizer {
+    pub fn new(config: &CrawlConfig) -> Self {
+        Self {
+            host_aliases: config.host_aliases.clone(),
+            canonicalize_www: config.canonicalize_www,
+            case_insensitive_paths: config.case_insensitive_paths,
+            index_files: config.index_files.clone(),
+            custom: config.normalize.clone(),
+        }
+    }
+
+    /// Fragments don't change what's fetched, so they're always dropped.
+    pub fn normalize(&self, mut url: Url) -> Url {
+        url.set_fragment(None);
+        if let Some(original) = url.host_str() {
+            let mut host = self
+                .host_aliases
+                .get(original)
+                .map_or(original, String::as_str);
+            if self.canonicalize_www {
+                host = host.strip_prefix("www.").unwrap_or(host);
+            }
+            if host != original {
+                let host = host.to_owned();
+                // only fails for hosts that can't be, which an alias might be
+                let _ = url.set_host(Some(&host));
+            }
+        }
+        if self.case_insensitive_paths {
+            let path = url.path().to_lowercase();
+            url.set_path(&path);
+        }
+        if let Some((dir, file)) = url.path().rsplit_once('/') {
+            if self.index_files.iter().any(|it| it == file) {
+                let dir = format!("{dir}/");
+                url.set_path(&dir);
+            }
+        }
+        match &self.custom {
+            Some(normalize) => normalize(url),
+            None => url,
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::time::Duration;
+use url::Url;
+
+/// The `Allow`, `Disallow` and `Crawl-delay` lines of a `robots.txt` that
+/// apply to one user agent.
+#[derive(Debug, Default)]
+pub(crate) struct Robots {
+    /// Each pattern, and whether it allows the paths it matches.
+    rules: Vec<(String, bool)>,
+    /// The least time between fetches. The last `Crawl-delay` wins.
+    pub crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    /// Keeps the lines of the groups naming `user_agent`, or of the `*` groups
+    /// if there are none. Agents are matched by whether `user_agent` contains
+    /// them, ignoring case, so `sprawl` matches `sprawl/1.0`.
+    pub fn parse(body: &str, user_agent: Option<&str>) -> Self {
+        let user_agent = user_agent.unwrap_or_default().to_ascii_lowercase();
+        let mut specific = None::<Robots>;
+        let mut wildcard = Robots::default();
+        let mut agents = Vec::new();
+        // consecutive `User-agent` lines share the rules that follow them
+        let mut in_agents = false;
+        for line in body.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if !in_agents {
+                        agents.clear();
+                    }
+                    in_agents = true;
+                    agents.push(value.to_ascii_lowercase());
+                }
+                key @ ("allow" | "disallow" | "crawl-delay") => {
+                    in_agents = false;
+                    // an empty `Disallow` allows everything
+                    let rule = (!value.is_empty()).then(|| (value.to_owned(), key == "allow"));
+                    // ignored if it isn't a number of seconds
+                    let delay = value
+                        .parse()
+                        .ok()
+                        .and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+                    for agent in &agents {
+                        let group = match agent.as_str() {
+                            "*" => &mut wildcard,
+                            agent if !agent.is_empty() && user_agent.contains(agent) => {
+                                specific.get_or_insert_with(Robots::default)
+                            }
+                            _ => continue,
+                        };
+                        match key {
+                            "crawl-delay" => group.crawl_delay = delay.or(group.crawl_delay),
+                            _ => group.rules.extend(rule.clone()),
+                        }
+                    }
+                }
+                _ => in_agents = false,
+            }
+        }
+        specific.unwrap_or(wildcard)
+    }
+
+    /// The longest matching rule decides, with `Allow` winning ties. Paths
+    /// without a matching rule are allowed.
+    pub fn allows(&self, url: &Url) -> bool {
+        let path = match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_owned(),
+        };
+        self.rules
+            .iter()
+            .filter(|(pattern, _)| matches(pattern, &path))
+            .max_by_key(|(pattern, allow)| (pattern.len(), *allow))
+            .is_none_or(|(_, allow)| *allow)
+    }
+}
+
+/// Whether `path` starts with `pattern`, where `*` matches anything and a
+/// trailing `$` matches the end of the path.
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = path.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts = Vec::from_iter(parts);
+    let Some((last, middle)) = parts.split_last() else {
+        return !anchored || rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    match anchored {
+        true => rest.ends_with(last),
+        false => rest.contains(last),
+    }
+}
@@ -0,0 +1,46 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// A token bucket holding up to a second's worth of bytes, shared by every
+/// fetch.
+pub(crate) struct Throttle {
+    bytes_per_sec: f64,
+    state: Mutex<State>,
+}
+
+struct State {
+    /// Negative once bytes have been read before they were available.
+    available: f64,
+    updated: Instant,
+}
+
+impl Throttle {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.max(1) as f64,
+            state: Mutex::new(State {
+                available: bytes_per_sec as f64,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until `bytes` more have been paid for, so that callers are
+    /// served in the order they call.
+    pub async fn consume(&self, bytes: usize) {
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            let refilled = now.duration_since(state.updated).as_secs_f64() * self.bytes_per_sec;
+            state.available = (state.available + refilled).min(self.bytes_per_sec);
+            state.updated = now;
+            state.available -= bytes as f64;
+            Duration::from_secs_f64((-state.available).max(0.0) / self.bytes_per_sec)
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}